@@ -0,0 +1,319 @@
+//! Patches in the BPS format.
+//!
+//! A BPS patch builds the target from copies of the source, of the target written so far and of
+//! bytes stored in the patch. It ends with the CRC32 checksums of the source, the target and the
+//! patch itself, so applying a patch to the wrong source or a damaged patch is detected.
+use crate::{
+    crc32,
+    patch::{split_checksums, verify_checksum, Reader},
+    Error,
+};
+
+const HEADER: &[u8] = b"BPS1";
+
+const SOURCE_READ: usize = 0;
+const TARGET_READ: usize = 1;
+const SOURCE_COPY: usize = 2;
+const TARGET_COPY: usize = 3;
+
+/// The shortest copy worth a command of its own.
+const MIN_COPY: usize = 4;
+/// The number of earlier positions tried for a copy.
+const MAX_CANDIDATES: usize = 0x40;
+
+/// Applies `patch` to `source` and returns the target.
+///
+/// Returns an error if the patch is malformed, or if the checksum of the patch, the source or the
+/// target does not match.
+///
+/// # Examples
+/// ```rust
+/// use binseg::bps;
+///
+/// let source = b"DEADBEEF";
+/// let patch = bps::create(source, b"DEADC0DE");
+///
+/// assert_eq!(bps::apply(&patch, source).unwrap(), b"DEADC0DE");
+/// assert!(bps::apply(&patch, b"BE57C0DE").is_err());
+/// ```
+///
+/// A patch declaring a target larger than its commands write is rejected without allocating the
+/// declared size:
+/// ```rust
+/// use binseg::{bps, crc32, Error};
+///
+/// let source = b"DEADBEEF";
+/// let mut patch = b"BPS1\x88\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x80\x80".to_vec();
+/// patch.extend_from_slice(&crc32(source).to_le_bytes());
+/// patch.extend_from_slice(&[0x00; 4]);
+/// patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+///
+/// assert!(matches!(bps::apply(&patch, source), Err(Error::InvalidPatch { .. })));
+/// ```
+///
+/// Damaged patches are detected by their checksums:
+/// ```rust
+/// use binseg::{bps, crc32, Error};
+///
+/// let source = b"DEADBEEF";
+/// let patch = bps::create(source, b"DEADC0DE");
+///
+/// let mut damaged = patch.clone();
+/// damaged[8] ^= 0x01;
+/// assert!(matches!(
+///     bps::apply(&damaged, source),
+///     Err(Error::PatchChecksumMismatch { file: "patch", .. })
+/// ));
+///
+/// let mut wrong_target = patch[..patch.len() - 8].to_vec();
+/// wrong_target.extend_from_slice(&crc32(b"DEADBEEF").to_le_bytes());
+/// wrong_target.extend_from_slice(&crc32(&wrong_target).to_le_bytes());
+/// assert!(matches!(
+///     bps::apply(&wrong_target, source),
+///     Err(Error::PatchChecksumMismatch { file: "target", .. })
+/// ));
+///
+/// assert!(bps::apply(&patch[..patch.len() - 1], source).is_err());
+/// ```
+///
+/// Commands reading past the end of the patch or past the target written so far are rejected,
+/// even if the checksums match:
+/// ```rust
+/// use binseg::{bps, crc32, Error};
+///
+/// let source = b"DEADBEEF";
+/// let with_checksums = |mut patch: Vec<u8>| {
+///     patch.extend_from_slice(&crc32(source).to_le_bytes());
+///     patch.extend_from_slice(&crc32(b"DEADC0DE").to_le_bytes());
+///     patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+///     patch
+/// };
+///
+/// // Reads 8 bytes from the patch, which has only 2 left.
+/// let truncated = with_checksums(b"BPS1\x88\x88\x80\x9dDE".to_vec());
+/// assert!(matches!(bps::apply(&truncated, source), Err(Error::InvalidPatch { offset: 7 })));
+///
+/// // Reads 1 byte from the patch, then copies 4 bytes of the target starting at offset 1.
+/// let past_target = with_checksums(b"BPS1\x88\x88\x80\x81D\x8f\x82".to_vec());
+/// assert!(matches!(bps::apply(&past_target, source), Err(Error::InvalidPatch { offset: 9 })));
+/// ```
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, Error> {
+    let (body, checksums) = split_checksums(patch, HEADER)?;
+    verify_checksum("source", checksums.source, crc32(source))?;
+
+    let mut reader = Reader::new(body, HEADER.len());
+    let invalid = |offset| Error::InvalidPatch { offset };
+
+    let source_size = reader.number().ok_or_else(|| invalid(reader.pos))?;
+    let target_size = reader.number().ok_or_else(|| invalid(reader.pos))?;
+    let metadata_size = reader.number().ok_or_else(|| invalid(reader.pos))?;
+    reader
+        .bytes(metadata_size)
+        .ok_or_else(|| invalid(reader.pos))?;
+
+    if source_size != source.len() {
+        return Err(Error::LengthMismatch {
+            expected: source_size,
+            actual: source.len(),
+        });
+    }
+
+    // The target size comes from the patch, so the target only grows as the commands write it.
+    let mut target = Vec::new();
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+
+    while reader.pos < body.len() {
+        let offset = reader.pos;
+        let command = reader.number().ok_or_else(|| invalid(offset))?;
+        let len = (command >> 2) + 1;
+        if target.len() + len > target_size {
+            return Err(invalid(offset));
+        }
+
+        let copied = match command & 0x03 {
+            SOURCE_READ => source
+                .get(target.len()..target.len() + len)
+                .map(|bytes| target.extend_from_slice(bytes)),
+            TARGET_READ => reader
+                .bytes(len)
+                .map(|bytes| target.extend_from_slice(bytes)),
+            SOURCE_COPY => reader
+                .relative(&mut source_offset, len)
+                .and_then(|start| source.get(start..start + len))
+                .map(|bytes| target.extend_from_slice(bytes)),
+            _ => reader
+                .relative(&mut target_offset, len)
+                .filter(|&start| start < target.len())
+                .map(|start| {
+                    for i in start..start + len {
+                        target.push(target[i]);
+                    }
+                }),
+        };
+        copied.ok_or_else(|| invalid(offset))?;
+    }
+
+    if target.len() != target_size {
+        return Err(invalid(reader.pos));
+    }
+    verify_checksum("target", checksums.target, crc32(&target))?;
+
+    Ok(target)
+}
+
+/// Creates a patch that turns `source` into `target`.
+///
+/// # Examples
+/// ```rust
+/// use binseg::bps;
+///
+/// let patch = bps::create(b"DEADBEEF", b"DEADBEEF");
+/// assert_eq!(&patch[..4], b"BPS1");
+/// ```
+pub fn create(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = HEADER.to_vec();
+    push_number(&mut patch, source.len());
+    push_number(&mut patch, target.len());
+    push_number(&mut patch, 0);
+
+    let source_chains = Chains::new(source, source.len());
+    let mut target_chains = Chains::new(target, 0);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    let mut literal = 0;
+    let mut pos = 0;
+
+    while pos < target.len() {
+        target_chains.insert_until(target, pos);
+
+        let mut best = (
+            SOURCE_READ,
+            common_len(source.get(pos..), &target[pos..]),
+            0,
+        );
+        for (command, data, chains) in [
+            (SOURCE_COPY, source, &source_chains),
+            (TARGET_COPY, target, &target_chains),
+        ] {
+            for start in chains.candidates(target, pos) {
+                let len = common_len(data.get(start..), &target[pos..]);
+                if len > best.1 {
+                    best = (command, len, start);
+                }
+            }
+        }
+
+        let (command, len, start) = best;
+        if len < MIN_COPY {
+            pos += 1;
+            continue;
+        }
+
+        if literal < pos {
+            push_number(&mut patch, ((pos - literal - 1) << 2) | TARGET_READ);
+            patch.extend_from_slice(&target[literal..pos]);
+        }
+        push_number(&mut patch, ((len - 1) << 2) | command);
+        match command {
+            SOURCE_COPY => push_relative(&mut patch, &mut source_offset, start, len),
+            TARGET_COPY => push_relative(&mut patch, &mut target_offset, start, len),
+            _ => {}
+        }
+        pos += len;
+        literal = pos;
+    }
+
+    if literal < pos {
+        push_number(&mut patch, ((pos - literal - 1) << 2) | TARGET_READ);
+        patch.extend_from_slice(&target[literal..pos]);
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+
+    patch
+}
+
+/// Returns the number of bytes `a` and `b` have in common at their start.
+fn common_len(a: Option<&[u8]>, b: &[u8]) -> usize {
+    a.map_or(0, |a| a.iter().zip(b).take_while(|(a, b)| a == b).count())
+}
+
+fn push_number(patch: &mut Vec<u8>, mut number: usize) {
+    loop {
+        let byte = (number & 0x7f) as u8;
+        number >>= 7;
+        if number == 0 {
+            patch.push(0x80 | byte);
+            return;
+        }
+        patch.push(byte);
+        number -= 1;
+    }
+}
+
+fn push_relative(patch: &mut Vec<u8>, offset: &mut usize, start: usize, len: usize) {
+    if start < *offset {
+        push_number(patch, ((*offset - start) << 1) | 1);
+    } else {
+        push_number(patch, (start - *offset) << 1);
+    }
+    *offset = start + len;
+}
+
+/// The positions of every `MIN_COPY` bytes of a file, chained by their hash.
+struct Chains {
+    heads: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Chains {
+    const BUCKETS: usize = 0x10000;
+    const NONE: usize = usize::MAX;
+
+    /// Creates the chains for the first `len` positions of `data`.
+    fn new(data: &[u8], len: usize) -> Chains {
+        let mut chains = Chains {
+            heads: vec![Chains::NONE; Chains::BUCKETS],
+            previous: Vec::with_capacity(data.len()),
+        };
+        chains.insert_until(data, len);
+
+        chains
+    }
+
+    fn insert_until(&mut self, data: &[u8], len: usize) {
+        while self.previous.len() < len {
+            let pos = self.previous.len();
+            match Chains::bucket(data, pos) {
+                Some(bucket) => {
+                    self.previous.push(self.heads[bucket]);
+                    self.heads[bucket] = pos;
+                }
+                None => self.previous.push(Chains::NONE),
+            }
+        }
+    }
+
+    /// Returns the latest positions that may start with the same bytes as `target` at `pos`.
+    fn candidates<'a>(&'a self, target: &[u8], pos: usize) -> impl Iterator<Item = usize> + 'a {
+        let mut next =
+            Chains::bucket(target, pos).map_or(Chains::NONE, |bucket| self.heads[bucket]);
+
+        std::iter::from_fn(move || {
+            let pos = next;
+            next = *self.previous.get(pos)?;
+            Some(pos)
+        })
+        .take(MAX_CANDIDATES)
+    }
+
+    fn bucket(data: &[u8], pos: usize) -> Option<usize> {
+        let bytes = data.get(pos..pos + MIN_COPY)?;
+        let hash = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        Some((hash.wrapping_mul(0x9e37_79b1) >> 16) as usize)
+    }
+}
@@ -0,0 +1,175 @@
+//! Encodings of the values typed segments and fields can hold.
+//!
+//! Single bytes are read as the primitive types [`u8`] and [`i8`]. Wider integers come in a
+//! little endian (`le`) and a big endian (`be`) flavour, like [`u16le`] or [`u24be`]. Arrays of
+//! encodings, like `[u16le; 16]`, decode to arrays of values.
+#![allow(non_camel_case_types)]
+
+pub use std::primitive::{i8, u8};
+
+/// An encoding of a value in a fixed number of bytes.
+pub trait Codec {
+    /// The decoded value.
+    type Value;
+
+    /// The number of bytes of an encoded value.
+    const SIZE: usize;
+
+    /// Decodes a value from `bytes`, which are exactly `SIZE` bytes long.
+    fn decode(bytes: &[u8]) -> Self::Value;
+
+    /// Encodes `value` into `bytes`, which are exactly `SIZE` bytes long.
+    fn encode(value: Self::Value, bytes: &mut [u8]);
+}
+
+impl Codec for u8 {
+    type Value = u8;
+
+    const SIZE: usize = 1;
+
+    fn decode(bytes: &[u8]) -> u8 {
+        bytes[0]
+    }
+
+    fn encode(value: u8, bytes: &mut [u8]) {
+        bytes[0] = value;
+    }
+}
+
+impl Codec for i8 {
+    type Value = i8;
+
+    const SIZE: usize = 1;
+
+    fn decode(bytes: &[u8]) -> i8 {
+        bytes[0] as i8
+    }
+
+    fn encode(value: i8, bytes: &mut [u8]) {
+        bytes[0] = value as u8;
+    }
+}
+
+macro_rules! int_codecs {
+    ($($(#[$meta:meta])* $codec:ident: $value:ident, $size:literal, $endian:ident;)*) => (
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy)]
+            pub enum $codec {}
+
+            impl Codec for $codec {
+                type Value = $value;
+
+                const SIZE: usize = $size;
+
+                fn decode(bytes: &[u8]) -> $value {
+                    let mut buf = [0; std::mem::size_of::<$value>()];
+                    int_codecs!(@decode $endian $value, buf, bytes)
+                }
+
+                fn encode(value: $value, bytes: &mut [u8]) {
+                    int_codecs!(@encode $endian value, bytes)
+                }
+            }
+        )*
+    );
+    (@decode le $value:ident, $buf:ident, $bytes:ident) => ({
+        $buf[..$bytes.len()].copy_from_slice($bytes);
+        $value::from_le_bytes($buf)
+    });
+    (@decode be $value:ident, $buf:ident, $bytes:ident) => ({
+        let start = $buf.len() - $bytes.len();
+        $buf[start..].copy_from_slice($bytes);
+        $value::from_be_bytes($buf)
+    });
+    (@encode le $value:ident, $bytes:ident) => ({
+        let len = $bytes.len();
+        $bytes.copy_from_slice(&$value.to_le_bytes()[..len]);
+    });
+    (@encode be $value:ident, $bytes:ident) => ({
+        let buf = $value.to_be_bytes();
+        $bytes.copy_from_slice(&buf[buf.len() - $bytes.len()..]);
+    });
+}
+
+int_codecs! {
+    /// A little endian `u16`.
+    u16le: u16, 2, le;
+    /// A big endian `u16`.
+    u16be: u16, 2, be;
+    /// A little endian 24 bit unsigned integer, like the long pointers of the SNES. Encoding
+    /// drops the most significant byte.
+    u24le: u32, 3, le;
+    /// A big endian 24 bit unsigned integer. Encoding drops the most significant byte.
+    u24be: u32, 3, be;
+    /// A little endian `u32`.
+    u32le: u32, 4, le;
+    /// A big endian `u32`.
+    u32be: u32, 4, be;
+    /// A little endian `u64`.
+    u64le: u64, 8, le;
+    /// A big endian `u64`.
+    u64be: u64, 8, be;
+    /// A little endian `i16`.
+    i16le: i16, 2, le;
+    /// A big endian `i16`.
+    i16be: i16, 2, be;
+    /// A little endian `i32`.
+    i32le: i32, 4, le;
+    /// A big endian `i32`.
+    i32be: i32, 4, be;
+    /// A little endian `i64`.
+    i64le: i64, 8, le;
+    /// A big endian `i64`.
+    i64be: i64, 8, be;
+}
+
+/// An unsigned integer whose bits can be read and written in ranges.
+pub trait Bits: Copy {
+    /// The number of bits of the integer.
+    const BITS: u32;
+
+    /// Returns the bits `lo..hi`, shifted down to bit 0.
+    fn get_bits(self, lo: u32, hi: u32) -> Self;
+
+    /// Replaces the bits `lo..hi` with the lowest bits of `value`.
+    fn set_bits(self, lo: u32, hi: u32, value: Self) -> Self;
+}
+
+macro_rules! bits {
+    ($($int:ident),*) => (
+        $(
+            impl Bits for $int {
+                const BITS: u32 = $int::BITS;
+
+                fn get_bits(self, lo: u32, hi: u32) -> $int {
+                    (self >> lo) & (<$int>::MAX >> ($int::BITS - (hi - lo)))
+                }
+
+                fn set_bits(self, lo: u32, hi: u32, value: $int) -> $int {
+                    let mask = (<$int>::MAX >> ($int::BITS - (hi - lo))) << lo;
+
+                    (self & !mask) | ((value << lo) & mask)
+                }
+            }
+        )*
+    );
+}
+
+bits!(u8, u16, u32, u64);
+
+impl<C: Codec, const N: usize> Codec for [C; N] {
+    type Value = [C::Value; N];
+
+    const SIZE: usize = C::SIZE * N;
+
+    fn decode(bytes: &[u8]) -> [C::Value; N] {
+        std::array::from_fn(|i| C::decode(&bytes[i * C::SIZE..(i + 1) * C::SIZE]))
+    }
+
+    fn encode(value: [C::Value; N], bytes: &mut [u8]) {
+        for (value, bytes) in IntoIterator::into_iter(value).zip(bytes.chunks_exact_mut(C::SIZE)) {
+            C::encode(value, bytes);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! The compression formats of Lunar Compress, which many SNES games store their graphics and
+//! tables in.
+//!
+//! Compressed data is a list of commands, ended by a `0xff` byte. Every command starts with a
+//! header `CCCLLLLL` for the command `C` and a length `L` of up to 32 bytes, or `111CCCLL
+//! LLLLLLLL` for a length of up to 1024 bytes.
+use crate::Error;
+use std::{collections::HashMap, fmt};
+
+/// A compression format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// LC_LZ2, used by Super Mario World and A Link to the Past.
+    Lz2,
+    /// LC_LZ3, used by Pokémon Gold and Silver and several SNES games.
+    Lz3,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Lz2 => write!(f, "LC_LZ2"),
+            Format::Lz3 => write!(f, "LC_LZ3"),
+        }
+    }
+}
+
+const END: u8 = 0xff;
+const LONG: u8 = 7;
+const MAX_LEN: usize = 0x400;
+/// The number of earlier positions the compressor tries for a repeat.
+const MAX_CANDIDATES: usize = 0x100;
+
+const COPY: u8 = 0;
+const BYTE_FILL: u8 = 1;
+const WORD_FILL: u8 = 2;
+/// An increasing fill in LC_LZ2 and a zero fill in LC_LZ3.
+const SEQUENCE_FILL: u8 = 3;
+const REPEAT: u8 = 4;
+const BIT_REVERSE_REPEAT: u8 = 5;
+const BACKWARDS_REPEAT: u8 = 6;
+
+/// Decompresses `data`, which may continue past the end of the compressed data.
+///
+/// Returns an error if `data` ends before the end of the compressed data, or if it contains an
+/// invalid command.
+///
+/// # Examples
+/// ```rust
+/// use binseg::compression::{decompress, Format};
+///
+/// // Copies two bytes, then fills three bytes with 0x07.
+/// let data = [0x01, 0x12, 0x34, 0x22, 0x07, 0xff];
+/// assert_eq!(decompress(Format::Lz2, &data).unwrap(), [0x12, 0x34, 0x07, 0x07, 0x07]);
+///
+/// // Fills three bytes alternating 0xab and 0xcd, then 64 bytes with 0x07 using a long header.
+/// let data = [0x42, 0xab, 0xcd, 0xe4, 0x3f, 0x07, 0xff];
+/// let output = decompress(Format::Lz2, &data).unwrap();
+/// assert_eq!(output[..3], [0xab, 0xcd, 0xab]);
+/// assert_eq!(output[3..], [0x07; 0x40]);
+/// ```
+///
+/// The commands that differ between the formats:
+/// ```rust
+/// use binseg::compression::{decompress, Format};
+///
+/// // LC_LZ2: Copies `ABC`, repeats four bytes from offset 0, then fills three bytes increasing
+/// // from 0x10.
+/// let data = [0x02, b'A', b'B', b'C', 0x83, 0x00, 0x00, 0x62, 0x10, 0xff];
+/// assert_eq!(decompress(Format::Lz2, &data).unwrap(), b"ABCABCA\x10\x11\x12");
+///
+/// // LC_LZ3: Copies `ABC`, repeats three bytes from two bytes back, repeats three bytes backwards
+/// // from the last byte, then fills two bytes with zeros.
+/// let data = [0x02, b'A', b'B', b'C', 0x82, 0x81, 0xc2, 0x80, 0x61, 0xff];
+/// assert_eq!(decompress(Format::Lz3, &data).unwrap(), b"ABCBCBBCB\x00\x00");
+///
+/// // LC_LZ3: Copies two bytes, then repeats them bit reversed from offset 0.
+/// let data = [0x01, 0x01, 0x80, 0xa1, 0x00, 0x00, 0xff];
+/// assert_eq!(decompress(Format::Lz3, &data).unwrap(), [0x01, 0x80, 0x80, 0x01]);
+/// ```
+///
+/// Data ending in the middle of a command is rejected:
+/// ```rust
+/// use binseg::{compression::{decompress, Format}, Error};
+///
+/// // Copies four bytes, but only one follows.
+/// let data = [0x03, 0x12];
+/// assert!(matches!(
+///     decompress(Format::Lz2, &data),
+///     Err(Error::InvalidCompressedData { format: Format::Lz2, offset: 0 })
+/// ));
+/// ```
+pub fn decompress(format: Format, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    let mut reader = Reader { data, pos: 0 };
+
+    loop {
+        let offset = reader.pos;
+        let invalid = || Error::InvalidCompressedData { format, offset };
+
+        let header = reader.byte().ok_or_else(invalid)?;
+        if header == END {
+            return Ok(output);
+        }
+
+        let (command, len) = if header >> 5 == LONG {
+            let low = reader.byte().ok_or_else(invalid)?;
+            let len = (usize::from(header & 0x03) << 8) | usize::from(low);
+            ((header >> 2) & 0x07, len + 1)
+        } else {
+            (header >> 5, usize::from(header & 0x1f) + 1)
+        };
+
+        let decoded =
+            match (format, command) {
+                (_, COPY) => reader
+                    .bytes(len)
+                    .map(|bytes| output.extend_from_slice(bytes)),
+                (_, BYTE_FILL) => reader
+                    .byte()
+                    .map(|byte| output.resize(output.len() + len, byte)),
+                (_, WORD_FILL) => reader
+                    .bytes(2)
+                    .map(|word| output.extend((0..len).map(|i| word[i % 2]))),
+                (Format::Lz2, SEQUENCE_FILL) => reader
+                    .byte()
+                    .map(|byte| output.extend((0..len).map(|i| byte.wrapping_add(i as u8)))),
+                (Format::Lz3, SEQUENCE_FILL) => {
+                    output.resize(output.len() + len, 0x00);
+                    Some(())
+                }
+                (Format::Lz2, REPEAT) => reader
+                    .bytes(2)
+                    .map(|source| usize::from(u16::from_be_bytes([source[0], source[1]])))
+                    .and_then(|source| repeat(&mut output, source, len, |s, i| Some(s + i), |b| b)),
+                (Format::Lz3, REPEAT..=BACKWARDS_REPEAT) => reader
+                    .lz3_source(output.len())
+                    .and_then(|source| match command {
+                        REPEAT => repeat(&mut output, source, len, |s, i| Some(s + i), |b| b),
+                        BIT_REVERSE_REPEAT => repeat(
+                            &mut output,
+                            source,
+                            len,
+                            |s, i| Some(s + i),
+                            u8::reverse_bits,
+                        ),
+                        _ => repeat(&mut output, source, len, usize::checked_sub, |b| b),
+                    }),
+                _ => None,
+            };
+        decoded.ok_or_else(invalid)?;
+    }
+}
+
+/// Reads the commands of compressed data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + count)?;
+        self.pos += count;
+
+        Some(bytes)
+    }
+
+    /// Reads the source of an LC_LZ3 repeat, which is either a distance back from the end of the
+    /// output or a big endian offset into the output.
+    fn lz3_source(&mut self, output_len: usize) -> Option<usize> {
+        let byte = self.byte()?;
+        if byte & 0x80 != 0 {
+            output_len.checked_sub(usize::from(byte & 0x7f) + 1)
+        } else {
+            Some((usize::from(byte) << 8) | usize::from(self.byte()?))
+        }
+    }
+}
+
+/// Appends `len` bytes of `output` to it, the `i`th one taken from `position(source, i)` and
+/// passed through `map`. Returns `None` if a position lies outside of the output.
+fn repeat(
+    output: &mut Vec<u8>,
+    source: usize,
+    len: usize,
+    position: impl Fn(usize, usize) -> Option<usize>,
+    map: impl Fn(u8) -> u8,
+) -> Option<()> {
+    for i in 0..len {
+        let byte = *output.get(position(source, i)?)?;
+        output.push(map(byte));
+    }
+
+    Some(())
+}
+
+/// Compresses `data`.
+///
+/// The compressor uses fills and forward repeats, but none of the bit reversed and backwards
+/// repeats of LC_LZ3.
+///
+/// # Examples
+/// ```rust
+/// use binseg::compression::{compress, decompress, Format};
+///
+/// let data = b"ABABABABABAB-ABABABABABAB-ABABABABABAB";
+///
+/// let compressed = compress(Format::Lz3, data);
+/// assert!(compressed.len() < data.len());
+/// assert_eq!(decompress(Format::Lz3, &compressed).unwrap(), data);
+/// ```
+pub fn compress(format: Format, data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut matches = Matches::default();
+    let mut literal = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        matches.insert_until(data, pos);
+
+        match best_command(format, data, pos, &matches) {
+            Some(command) => {
+                write_copies(&mut output, &data[literal..pos]);
+                command.write(&mut output);
+                pos += command.len;
+                literal = pos;
+            }
+            None => pos += 1,
+        }
+    }
+
+    write_copies(&mut output, &data[literal..]);
+    output.push(END);
+
+    output
+}
+
+/// Compresses `data` into the segment `segment` with the bytes `segment_bytes`, and fills the rest
+/// of the segment with `fill`. Returns the size of the compressed data.
+///
+/// Returns an error and leaves the segment untouched if the compressed data does not fit.
+#[doc(hidden)]
+pub fn compress_into(
+    segment: &'static str,
+    format: Format,
+    data: &[u8],
+    segment_bytes: &mut [u8],
+    fill: u8,
+) -> Result<usize, Error> {
+    let compressed = compress(format, data);
+    if compressed.len() > segment_bytes.len() {
+        return Err(Error::DoesNotFit {
+            segment,
+            size: compressed.len(),
+            available: segment_bytes.len(),
+        });
+    }
+
+    let (head, rest) = segment_bytes.split_at_mut(compressed.len());
+    head.copy_from_slice(&compressed);
+    rest.fill(fill);
+
+    Ok(compressed.len())
+}
+
+/// A command found by the compressor.
+struct Command {
+    command: u8,
+    len: usize,
+    args: Vec<u8>,
+}
+
+impl Command {
+    /// Returns the number of bytes the command takes up in the compressed data.
+    fn size(&self) -> usize {
+        header_size(self.len) + self.args.len()
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        write_header(output, self.command, self.len);
+        output.extend_from_slice(&self.args);
+    }
+}
+
+fn header_size(len: usize) -> usize {
+    if len > 0x20 {
+        2
+    } else {
+        1
+    }
+}
+
+fn write_header(output: &mut Vec<u8>, command: u8, len: usize) {
+    let len = len - 1;
+    if len >= 0x20 {
+        output.push((LONG << 5) | (command << 2) | (len >> 8) as u8);
+        output.push(len as u8);
+    } else {
+        output.push((command << 5) | len as u8);
+    }
+}
+
+fn write_copies(output: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(MAX_LEN) {
+        write_header(output, COPY, chunk.len());
+        output.extend_from_slice(chunk);
+    }
+}
+
+/// The positions of every three bytes seen so far, for finding repeats.
+#[derive(Default)]
+struct Matches {
+    positions: HashMap<[u8; 3], Vec<usize>>,
+    inserted: usize,
+}
+
+impl Matches {
+    fn insert_until(&mut self, data: &[u8], pos: usize) {
+        while self.inserted < pos {
+            if let Some(key) = data.get(self.inserted..self.inserted + 3) {
+                self.positions
+                    .entry([key[0], key[1], key[2]])
+                    .or_default()
+                    .push(self.inserted);
+            }
+            self.inserted += 1;
+        }
+    }
+
+    fn get(&self, data: &[u8], pos: usize) -> &[usize] {
+        data.get(pos..pos + 3)
+            .and_then(|key| self.positions.get(&[key[0], key[1], key[2]]))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Finds the command that saves the most bytes at `pos`, if any command saves bytes at all.
+fn best_command(format: Format, data: &[u8], pos: usize, matches: &Matches) -> Option<Command> {
+    let run = |byte_at: &dyn Fn(usize) -> u8| {
+        data[pos..]
+            .iter()
+            .take(MAX_LEN)
+            .enumerate()
+            .take_while(|&(i, &byte)| byte == byte_at(i))
+            .count()
+    };
+    let first = data[pos];
+    let second = data.get(pos + 1).copied().unwrap_or(first);
+
+    let mut commands = vec![
+        Command {
+            command: BYTE_FILL,
+            len: run(&|_| first),
+            args: vec![first],
+        },
+        Command {
+            command: WORD_FILL,
+            len: run(&|i| if i % 2 == 0 { first } else { second }),
+            args: vec![first, second],
+        },
+        match format {
+            Format::Lz2 => Command {
+                command: SEQUENCE_FILL,
+                len: run(&|i| first.wrapping_add(i as u8)),
+                args: vec![first],
+            },
+            Format::Lz3 => Command {
+                command: SEQUENCE_FILL,
+                len: run(&|_| 0x00),
+                args: Vec::new(),
+            },
+        },
+    ];
+
+    let max_source = match format {
+        Format::Lz2 => 0xffff,
+        Format::Lz3 => 0x7fff,
+    };
+    for &source in matches.get(data, pos).iter().rev().take(MAX_CANDIDATES) {
+        let len = data[pos..]
+            .iter()
+            .take(MAX_LEN)
+            .zip(&data[source..])
+            .take_while(|(byte, source_byte)| byte == source_byte)
+            .count();
+
+        let args = match format {
+            Format::Lz3 if pos - source <= 0x80 => vec![0x80 | (pos - source - 1) as u8],
+            _ if source <= max_source => (source as u16).to_be_bytes().to_vec(),
+            _ => continue,
+        };
+        commands.push(Command {
+            command: REPEAT,
+            len,
+            args,
+        });
+
+        if len == MAX_LEN {
+            break;
+        }
+    }
+
+    commands
+        .into_iter()
+        .filter(|command| command.len > command.size())
+        .max_by_key(|command| command.len - command.size())
+}
@@ -0,0 +1,267 @@
+use crate::{compression::Format, HashAlgorithm};
+use std::{error, fmt, io, ops::Range};
+
+/// The error type for loading and accessing segmented binaries.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a binary or a patch failed.
+    Io(io::Error),
+    /// The hash of the binary does not match the hash the segmenter was defined with.
+    HashMismatch {
+        /// The algorithm of the mismatching hash.
+        algorithm: HashAlgorithm,
+        /// The hex digest given in the segmenter definition.
+        expected: String,
+        /// The hex digest of the loaded binary.
+        actual: String,
+    },
+    /// The binary does not have the expected length.
+    LengthMismatch {
+        /// The expected length.
+        expected: usize,
+        /// The length of the loaded binary.
+        actual: usize,
+    },
+    /// The binary does not contain the expected magic bytes.
+    MagicMismatch {
+        /// The offset of the magic bytes.
+        offset: usize,
+        /// The expected magic bytes.
+        expected: &'static [u8],
+        /// The bytes found at the offset, cut short if the binary ends early.
+        actual: Vec<u8>,
+    },
+    /// A custom validator rejected the binary.
+    ValidationFailed {
+        /// The name of the validator.
+        validator: &'static str,
+    },
+    /// The binary matches none of the revisions the segmenter was defined with. Contains the
+    /// mismatch of every revision, in order.
+    NoMatchingRevision(Vec<Error>),
+    /// A segment range reaches past the end of the binary.
+    SegmentOutOfBounds {
+        /// The name of the segment.
+        segment: &'static str,
+        /// The declared range of the segment.
+        range: Range<usize>,
+        /// The length of the loaded binary.
+        len: usize,
+    },
+    /// The SNES addresses of a segment do not map to a contiguous range of the binary.
+    UnmappedSegment {
+        /// The name of the segment.
+        segment: &'static str,
+        /// The declared range of the segment.
+        range: Range<usize>,
+    },
+    /// A pointer segment refers to a pointer past the end of the segment holding the pointers.
+    PointerOutOfBounds {
+        /// The name of the pointer segment.
+        segment: &'static str,
+        /// The name of the segment holding the pointers.
+        source: &'static str,
+        /// The index of the pointer.
+        index: usize,
+    },
+    /// A pointer of a pointer table segment points outside of the binary, or to a SNES address
+    /// that does not map to it.
+    InvalidPointer {
+        /// The name of the pointer segment.
+        segment: &'static str,
+        /// The index of the pointer.
+        index: usize,
+        /// The range the pointer points to.
+        range: Range<usize>,
+    },
+    /// A terminated segment has no terminator.
+    MissingTerminator {
+        /// The name of the segment.
+        segment: &'static str,
+        /// The range of the binary that was searched for the terminator.
+        range: Range<usize>,
+    },
+    /// Compressed data is cut short or contains an invalid command.
+    InvalidCompressedData {
+        /// The format of the compressed data.
+        format: Format,
+        /// The offset of the invalid command in the compressed data.
+        offset: usize,
+    },
+    /// Data compressed into a segment is larger than the segment.
+    DoesNotFit {
+        /// The name of the segment.
+        segment: &'static str,
+        /// The size of the compressed data.
+        size: usize,
+        /// The size of the segment.
+        available: usize,
+    },
+    /// A patch would have to change a byte at an offset the IPS format can not reach.
+    IpsOffsetOutOfRange {
+        /// The offset of the byte.
+        offset: usize,
+    },
+    /// A patch is cut short or contains invalid data.
+    InvalidPatch {
+        /// The offset of the invalid data in the patch.
+        offset: usize,
+    },
+    /// A checksum stored in a patch does not match.
+    PatchChecksumMismatch {
+        /// The file the checksum is for, `"source"`, `"target"` or `"patch"`.
+        file: &'static str,
+        /// The checksum stored in the patch.
+        expected: u32,
+        /// The checksum of the file.
+        actual: u32,
+    },
+    /// A segment has no range for the revision of the loaded binary.
+    NoRangeForRevision {
+        /// The name of the segment.
+        segment: &'static str,
+        /// The name of the loaded revision, if it is known and has a name.
+        revision: Option<&'static str>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not read or write file: {}", err),
+            Error::HashMismatch {
+                algorithm,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "incorrect file: expected {} hash {}, found {}",
+                algorithm, expected, actual
+            ),
+            Error::LengthMismatch { expected, actual } => write!(
+                f,
+                "incorrect file: expected a length of {:#x}, found {:#x}",
+                expected, actual
+            ),
+            Error::MagicMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "incorrect file: expected {:02x?} at {:#x}, found {:02x?}",
+                expected, offset, actual
+            ),
+            Error::ValidationFailed { validator } => {
+                write!(f, "incorrect file: rejected by `{}`", validator)
+            }
+            Error::NoMatchingRevision(mismatches) => {
+                write!(f, "binary does not match any known revision")?;
+                for mismatch in mismatches {
+                    write!(f, "; {}", mismatch)?;
+                }
+                Ok(())
+            }
+            Error::SegmentOutOfBounds {
+                segment,
+                range,
+                len,
+            } => write!(
+                f,
+                "segment `{}` ({:#x}..{:#x}) is out of bounds for a binary of length {:#x}",
+                segment, range.start, range.end, len
+            ),
+            Error::UnmappedSegment { segment, range } => write!(
+                f,
+                "segment `{}` (${:06X}..${:06X}) does not map to a contiguous range of the binary",
+                segment, range.start, range.end
+            ),
+            Error::PointerOutOfBounds {
+                segment,
+                source,
+                index,
+            } => write!(
+                f,
+                "segment `{}` refers to pointer {} past the end of segment `{}`",
+                segment, index, source
+            ),
+            Error::InvalidPointer {
+                segment,
+                index,
+                range,
+            } => write!(
+                f,
+                "pointer {} of segment `{}` points to {:#x}..{:#x}, which is not in the binary",
+                index, segment, range.start, range.end
+            ),
+            Error::MissingTerminator { segment, range } => write!(
+                f,
+                "segment `{}` has no terminator in {:#x}..{:#x}",
+                segment, range.start, range.end
+            ),
+            Error::InvalidCompressedData { format, offset } => write!(
+                f,
+                "invalid {} data: bad or truncated command at {:#x}",
+                format, offset
+            ),
+            Error::DoesNotFit {
+                segment,
+                size,
+                available,
+            } => write!(
+                f,
+                "compressed data does not fit segment `{}` by {} bytes ({:#x} bytes for {:#x})",
+                segment,
+                size - available,
+                size,
+                available
+            ),
+            Error::IpsOffsetOutOfRange { offset } => {
+                write!(f, "offset {:#x} is out of range for an IPS patch", offset)
+            }
+            Error::InvalidPatch { offset } => {
+                write!(f, "invalid patch: bad or truncated data at {:#x}", offset)
+            }
+            Error::PatchChecksumMismatch {
+                file,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "incorrect {}: expected crc32 {:08x} from the patch, found {:08x}",
+                file, expected, actual
+            ),
+            Error::NoRangeForRevision {
+                segment,
+                revision: Some(revision),
+            } => write!(
+                f,
+                "segment `{}` has no range for revision `{}`",
+                segment, revision
+            ),
+            Error::NoRangeForRevision {
+                segment,
+                revision: None,
+            } => write!(
+                f,
+                "segment `{}` has no range for an unknown revision",
+                segment
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
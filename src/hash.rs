@@ -0,0 +1,73 @@
+use crypto_hash::{hex_digest, Algorithm};
+use std::fmt;
+
+/// The hash algorithms a binary can be identified with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// The CRC-32 checksum used by zip, PNG and most ROM databases.
+    Crc32,
+    /// MD5
+    Md5,
+    /// SHA-1
+    Sha1,
+    /// SHA-256
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Returns the lowercase hex digest of `data`.
+    pub fn hex_digest(self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Crc32 => format!("{:08x}", crc32(data)),
+            HashAlgorithm::Md5 => hex_digest(Algorithm::MD5, data),
+            HashAlgorithm::Sha1 => hex_digest(Algorithm::SHA1, data),
+            HashAlgorithm::Sha256 => hex_digest(Algorithm::SHA256, data),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        })
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-32 (ISO-HDLC) checksum of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Returns whether `data` matches all of the given hex digests.
+#[doc(hidden)]
+pub fn verify_digests(data: &[u8], digests: &[(HashAlgorithm, &str)]) -> bool {
+    digests
+        .iter()
+        .all(|(algorithm, expected)| expected.eq_ignore_ascii_case(&algorithm.hex_digest(data)))
+}
@@ -0,0 +1,253 @@
+//! Patches in the IPS format.
+//!
+//! An IPS patch starts with `PATCH` and is a list of records, ended by `EOF`. A record is a 24 bit
+//! big endian offset, a 16 bit big endian size and that many bytes to write at the offset. A
+//! record with a size of zero is run length encoded instead, and writes a 16 bit big endian count
+//! of a single byte. After `EOF`, a patch may give a 24 bit big endian length to truncate the
+//! target to.
+use crate::{patch::Reader, Error};
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+
+/// The offset that reads like the `EOF` footer and can not start a record.
+const EOF_OFFSET: usize = 0x454f46;
+/// The first offset that does not fit into 24 bits.
+const MAX_OFFSET: usize = 0x100_0000;
+const MAX_SIZE: usize = 0xffff;
+/// The shortest run that is worth a run length encoded record of its own. Splitting a record
+/// costs the header of the record following the run, so shorter runs stay part of their records.
+const MIN_RUN: usize = 0x0e;
+/// The longest run of unchanged bytes that is cheaper to repeat than to start a new record for.
+const MAX_GAP: usize = 0x05;
+
+/// Applies `patch` to `source` and returns the target.
+///
+/// Returns an error if the patch is malformed.
+///
+/// # Examples
+/// ```rust
+/// use binseg::ips;
+///
+/// let patch = b"PATCH\x00\x00\x01\x00\x02\x12\x34\x00\x00\x04\x00\x00\x00\x02\xffEOF";
+/// let target = ips::apply(patch, &[0x00; 4]).unwrap();
+/// assert_eq!(target, [0x00, 0x12, 0x34, 0x00, 0xff, 0xff]);
+/// ```
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, Error> {
+    if !patch.starts_with(HEADER) {
+        return Err(Error::InvalidPatch { offset: 0 });
+    }
+
+    let mut target = source.to_vec();
+    let mut reader = Reader::new(patch, HEADER.len());
+
+    loop {
+        let offset = reader.pos;
+        let invalid = || Error::InvalidPatch { offset };
+
+        if reader.bytes(FOOTER.len()) == Some(FOOTER) {
+            break;
+        }
+        reader.pos = offset;
+
+        let start = reader.be(3).ok_or_else(invalid)?;
+        let data = match reader.be(2).ok_or_else(invalid)? {
+            0 => {
+                let count = reader.be(2).ok_or_else(invalid)?;
+                let byte = reader.bytes(1).ok_or_else(invalid)?[0];
+                vec![byte; count]
+            }
+            size => reader.bytes(size).ok_or_else(invalid)?.to_vec(),
+        };
+
+        let end = start + data.len();
+        if target.len() < end {
+            target.resize(end, 0x00);
+        }
+        target[start..end].copy_from_slice(&data);
+    }
+
+    if let Some(len) = reader.be(3) {
+        target.truncate(len);
+    }
+
+    Ok(target)
+}
+
+/// Creates a patch that turns `source` into `target`.
+///
+/// Returns an error if `target` differs from `source` at or past the offset of 16 MiB, which IPS
+/// can not reach.
+///
+/// # Examples
+/// ```rust
+/// use binseg::ips;
+///
+/// let patch = ips::create(&[0x00; 4], &[0x00, 0x12, 0x34, 0x00]).unwrap();
+/// assert_eq!(patch, b"PATCH\x00\x00\x01\x00\x02\x12\x34EOF");
+/// assert_eq!(ips::apply(&patch, &[0x00; 4]).unwrap(), [0x00, 0x12, 0x34, 0x00]);
+/// ```
+///
+/// Long runs of a single byte are run length encoded in a record with a size of zero:
+/// ```rust
+/// use binseg::ips;
+///
+/// let mut target = [0x00; 0x20];
+/// target[0x10..].fill(0xff);
+///
+/// let patch = ips::create(&[0x00; 0x20], &target).unwrap();
+/// assert_eq!(patch, b"PATCH\x00\x00\x10\x00\x00\x00\x10\xffEOF");
+/// assert_eq!(ips::apply(&patch, &[0x00; 0x20]).unwrap(), target);
+/// ```
+///
+/// A record at offset `0x454f46` would read as the `EOF` footer, so it starts a byte earlier:
+/// ```rust
+/// use binseg::ips;
+///
+/// let source = vec![0x00; 0x454f47];
+/// let mut target = source.clone();
+/// target[0x454f46] = 0x01;
+///
+/// let patch = ips::create(&source, &target).unwrap();
+/// assert_eq!(patch, b"PATCH\x45\x4f\x45\x00\x02\x00\x01EOF");
+/// assert_eq!(ips::apply(&patch, &source).unwrap(), target);
+/// ```
+///
+/// A target shorter than the source is truncated after the footer:
+/// ```rust
+/// use binseg::ips;
+///
+/// let patch = ips::create(&[0x00; 8], &[0x00; 4]).unwrap();
+/// assert_eq!(patch, b"PATCHEOF\x00\x00\x04");
+/// assert_eq!(ips::apply(&patch, &[0x00; 8]).unwrap(), [0x00; 4]);
+/// ```
+///
+/// Differences at or past 16 MiB can not be patched:
+/// ```rust
+/// use binseg::{ips, Error};
+///
+/// let source = vec![0x00; 0x100_0001];
+/// let mut target = source.clone();
+/// target[0x100_0000] = 0x01;
+///
+/// assert!(matches!(
+///     ips::create(&source, &target),
+///     Err(Error::IpsOffsetOutOfRange { offset: 0x100_0000 })
+/// ));
+/// ```
+pub fn create(source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut patch = HEADER.to_vec();
+    let differs = |offset: usize| source.get(offset) != target.get(offset);
+
+    let mut offset = 0;
+    while offset < target.len() {
+        if !differs(offset) {
+            offset += 1;
+            continue;
+        }
+
+        // Extends the record over short runs of unchanged bytes.
+        let mut end = offset + 1;
+        let mut gap = 0;
+        while end + gap < target.len() && gap <= MAX_GAP {
+            if differs(end + gap) {
+                end += gap + 1;
+                gap = 0;
+            } else {
+                gap += 1;
+            }
+        }
+
+        push_records(&mut patch, target, offset..end)?;
+        offset = end;
+    }
+
+    patch.extend_from_slice(FOOTER);
+    if target.len() < source.len() {
+        if target.len() >= MAX_OFFSET {
+            return Err(Error::IpsOffsetOutOfRange {
+                offset: target.len(),
+            });
+        }
+        patch.extend_from_slice(&be24(target.len()));
+    }
+
+    Ok(patch)
+}
+
+/// Writes the records for the bytes of `target` in `range`, run length encoding long runs.
+fn push_records(
+    patch: &mut Vec<u8>,
+    target: &[u8],
+    range: std::ops::Range<usize>,
+) -> Result<(), Error> {
+    let mut start = range.start;
+    let mut offset = range.start;
+
+    while offset < range.end {
+        let run = target[offset..range.end]
+            .iter()
+            .take_while(|&&byte| byte == target[offset])
+            .count();
+
+        if run >= MIN_RUN && offset != EOF_OFFSET {
+            push_data(patch, target, start..offset)?;
+            push_run(patch, offset, run.min(MAX_SIZE), target[offset])?;
+            offset += run.min(MAX_SIZE);
+            start = offset;
+        } else {
+            offset += run;
+        }
+    }
+
+    push_data(patch, target, start..range.end)
+}
+
+/// Writes records that copy the bytes of `target` in `range`.
+fn push_data(
+    patch: &mut Vec<u8>,
+    target: &[u8],
+    range: std::ops::Range<usize>,
+) -> Result<(), Error> {
+    let mut offset = range.start;
+
+    while offset < range.end {
+        // A record at the offset of the footer starts a byte early instead.
+        let start = if offset == EOF_OFFSET {
+            offset - 1
+        } else {
+            offset
+        };
+        let end = range.end.min(start + MAX_SIZE);
+
+        push_offset(patch, start)?;
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&target[start..end]);
+        offset = end;
+    }
+
+    Ok(())
+}
+
+/// Writes a run length encoded record of `count` times `byte`.
+fn push_run(patch: &mut Vec<u8>, offset: usize, count: usize, byte: u8) -> Result<(), Error> {
+    push_offset(patch, offset)?;
+    patch.extend_from_slice(&[0x00, 0x00]);
+    patch.extend_from_slice(&(count as u16).to_be_bytes());
+    patch.push(byte);
+
+    Ok(())
+}
+
+fn push_offset(patch: &mut Vec<u8>, offset: usize) -> Result<(), Error> {
+    if offset >= MAX_OFFSET {
+        return Err(Error::IpsOffsetOutOfRange { offset });
+    }
+    patch.extend_from_slice(&be24(offset));
+
+    Ok(())
+}
+
+fn be24(value: usize) -> [u8; 3] {
+    [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
@@ -4,6 +4,36 @@
 //! For the main part of this library go to the [segment_binary](macro.segment_binary.html) macro.
 
 pub use crypto_hash;
+#[doc(hidden)]
+pub use paste;
+
+pub mod bps;
+pub mod codec;
+pub mod compression;
+mod error;
+mod hash;
+pub mod ips;
+mod patch;
+mod revision;
+mod segment;
+pub mod snes;
+pub mod ups;
+
+pub use crate::codec::Codec;
+pub use crate::error::Error;
+#[doc(hidden)]
+pub use crate::hash::verify_digests;
+pub use crate::hash::{crc32, HashAlgorithm};
+pub use crate::patch::apply_patch;
+#[doc(hidden)]
+pub use crate::patch::apply_patch_to_stripped;
+#[doc(hidden)]
+pub use crate::revision::{has_revision, identify, Check, Revision};
+#[doc(hidden)]
+pub use crate::segment::{
+    check_literal_segments, resolve_pointer, resolve_pointer_table, resolve_range, resolve_table,
+    resolve_terminated, LiteralSegment,
+};
 
 /// Create a new binary segmenter for a binary with the given hash.
 ///
@@ -50,45 +80,1929 @@ pub use crypto_hash;
 /// assert_eq!(seq_bin.dead_beef(), &[0xde, 0xad, 0xbe, 0xef]);
 /// assert_eq!(seq_bin.best_code(), &[0xbe, 0x57, 0xc0, 0xde]);
 /// ```
-#[macro_export]
-macro_rules! segment_binary {
-    (
-        pub $bin_ident:ident ( $hash_string:literal ) {
-            $(
-                $(#[$meta_attr:meta])*
-                $seg_ident:ident : $mem_range:expr
-            ),*
-        }
-    ) => (
-        pub struct $bin_ident {
-            bin_data: Vec<u8>
-        }
-
-        impl $bin_ident {
-            #[doc = "Creates a new segmentation for the binary with the sha256 hash `"]
-            #[doc = $hash_string]
-            #[doc = "`"]
-            pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<$bin_ident> {
-                use std::{io::Read, fs};
-                use $crate::crypto_hash::{Algorithm, hex_digest};
-
-                let bin_data = fs::read(path)?;
-
-                let given_hash = String::from($hash_string);
-
-                let actual_file_hash = hex_digest(Algorithm::SHA256, &bin_data);
-
-                assert_eq!(actual_file_hash, given_hash, "incorrect file");
-
-                Ok($bin_ident { bin_data })
-            }
-
-            $(
-                $(#[$meta_attr])*
-                pub fn $seg_ident(&self) -> &[u8] {
-                    &self.bin_data[$mem_range]
-                }
-            )*
+///
+/// # Errors
+/// `from_file` returns an [`Error`](enum.Error.html) instead of panicking if the file can not be
+/// read or does not have the expected hash. Use `from_file_unchecked` to skip the hash check:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub OtherBin("0000000000000000000000000000000000000000000000000000000000000000") {
+///         dead_beef: 0x00..0x04
+///     }
+/// }
+///
+/// match OtherBin::from_file("test_bins/beef.bin") {
+///     Err(Error::HashMismatch { actual, .. }) => assert_eq!(
+///         actual,
+///         "8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4"
+///     ),
+///     _ => panic!("expected a hash mismatch"),
+/// }
+///
+/// let other_bin = OtherBin::from_file_unchecked("test_bins/beef.bin").unwrap();
+/// assert_eq!(other_bin.dead_beef(), &[0xde, 0xad, 0xbe, 0xef]);
+/// ```
+///
+/// Binaries that are already in memory can be segmented with `from_bytes`, `from_slice` or
+/// `from_reader`, which check the hash just like `from_file`:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// # segment_binary! {
+/// #     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+/// #         dead_beef: 0x00..0x04,
+/// #         best_code: 0x04..0x08
+/// #     }
+/// # }
+/// let beef = [0xde, 0xad, 0xbe, 0xef, 0xbe, 0x57, 0xc0, 0xde];
+///
+/// assert!(BeefBin::from_slice(&beef).is_ok());
+/// assert!(BeefBin::from_reader(&beef[..]).is_ok());
+/// assert!(BeefBin::from_bytes(beef[..4].to_vec()).is_err());
+/// ```
+///
+/// Every segment range is checked against the length of the binary when it is loaded, so the
+/// accessors never panic. A range that does not fit results in an error naming the segment:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub ShortBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x00..0x04,
+///         too_long: 0x04..0x10
+///     }
+/// }
+///
+/// match ShortBin::from_file("test_bins/beef.bin") {
+///     Err(Error::SegmentOutOfBounds { segment, .. }) => assert_eq!(segment, "too_long"),
+///     _ => panic!("expected an out of bounds segment"),
+/// }
+/// ```
+///
+/// # Modifying binaries
+/// For every segment `seg` there also is a `seg_mut` accessor. The modified binary can be written
+/// back with `write_to` or `write_to_file`:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// # segment_binary! {
+/// #     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+/// #         dead_beef: 0x00..0x04,
+/// #         best_code: 0x04..0x08
+/// #     }
+/// # }
+/// let mut seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// seq_bin.best_code_mut().copy_from_slice(&[0xca, 0xfe, 0xba, 0xbe]);
+///
+/// let mut out = Vec::new();
+/// seq_bin.write_to(&mut out).unwrap();
+///
+/// assert_eq!(out, [0xde, 0xad, 0xbe, 0xef, 0xca, 0xfe, 0xba, 0xbe]);
+/// assert_ne!(seq_bin.sha256(), "8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4");
+/// ```
+///
+/// # Revisions
+/// A segmenter can accept several hashes, for example for different revisions of the same game
+/// that share a layout. Every hash can be tagged with the name of its revision, and `revision`
+/// reports which one was loaded:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin(
+///         raw = "0000000000000000000000000000000000000000000000000000000000000000",
+///         cooked = "8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4",
+///     ) {
+///         dead_beef: 0x00..0x04
+///     }
+/// }
+///
+/// let seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.revision(), Some("cooked"));
+/// ```
+///
+/// If the offset of a segment differs between revisions, the segment can list a range per
+/// revision name. The accessor then uses the range of the loaded revision:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin(
+///         raw = "0000000000000000000000000000000000000000000000000000000000000000",
+///         cooked = "8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4",
+///     ) {
+///         best_code: {
+///             raw: 0x00..0x04,
+///             cooked: 0x04..=0x07,
+///         }
+///     }
+/// }
+///
+/// let seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.best_code(), &[0xbe, 0x57, 0xc0, 0xde]);
+/// ```
+///
+/// A range for a revision that is not declared fails to compile:
+/// ```compile_fail
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin(
+///         raw = "0000000000000000000000000000000000000000000000000000000000000000",
+///         cooked = "8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4",
+///     ) {
+///         best_code: {
+///             rwa: 0x00..0x04,
+///             cooked: 0x04..0x08,
+///         }
+///     }
+/// }
+/// ```
+///
+/// # Hash algorithms
+/// Plain hashes are sha256 hashes. Other algorithms can be selected with `sha256("...")`,
+/// `sha1("...")`, `md5("...")` and `crc32("...")`. A revision can require several hashes at once
+/// by joining them with `+`:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin(
+///         cooked = crc32("e44202e3") + md5("0e3cef0b5b2eba0567700f4606c2e89a")
+///     ) {
+///         dead_beef: 0x00..0x04
+///     }
+/// }
+///
+/// let seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.revision(), Some("cooked"));
+/// ```
+///
+/// # Structural checks
+/// Binaries without a fixed identity, like save files or modified roms, can be segmented by
+/// leaving out the hashes. Alternatively, the revisions can be identified by structural checks:
+/// `len(...)` checks the length of the binary, `magic(offset, b"...")` checks the bytes at an
+/// offset and `validate(path::to::function)` calls a `fn(&[u8]) -> bool`:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         dead_beef: 0x00..0x04
+///     }
+/// }
+///
+/// segment_binary! {
+///     pub BeefBin(len(8) + magic(0x00, b"\xde\xad")) {
+///         dead_beef: 0x00..0x04
+///     }
+/// }
+///
+/// assert!(AnyBin::from_slice(&[0x00; 4]).is_ok());
+/// assert!(BeefBin::from_slice(&[0x00; 8]).is_err());
+/// assert!(BeefBin::from_file("test_bins/beef.bin").is_ok());
+/// ```
+///
+/// A segmenter without segments only identifies the binary:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {}
+/// }
+///
+/// assert!(BeefBin::from_file("test_bins/beef.bin").is_ok());
+/// ```
+///
+/// # Typed segments
+/// Instead of a range, a segment can be declared with a type and an offset, like
+/// `count: u16le @ 0x10`. The accessor then decodes the value, and a `set_` method encodes it
+/// back into the binary. See the [codec](codec/index.html) module for the available types:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead: u16be @ 0x00,
+///         beef: [u8; 2] @ 0x02,
+///         best_code: u32le @ 0x04
+///     }
+/// }
+///
+/// let mut seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.dead(), 0xdead);
+/// assert_eq!(seq_bin.beef(), [0xbe, 0xef]);
+/// assert_eq!(seq_bin.best_code(), 0xdec057be);
+///
+/// seq_bin.set_best_code(0xc0ffee);
+/// assert_eq!(&seq_bin.as_bytes()[4..], &[0xee, 0xff, 0xc0, 0x00]);
+/// ```
+///
+/// # Tables
+/// Tables of fixed-size records are declared with `table(base, stride n, count n)`. The
+/// accessors of a table take the index of a record, and there are `_len`, `_iter` and
+/// `_iter_mut` methods:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         words: table(0x00, stride 2, count 4)
+///     }
+/// }
+///
+/// let mut seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.words_len(), 4);
+/// assert_eq!(seq_bin.words(1), &[0xbe, 0xef]);
+///
+/// for word in seq_bin.words_iter_mut() {
+///     word.swap(0, 1);
+/// }
+/// assert_eq!(seq_bin.words(3), &[0xde, 0xc0]);
+/// ```
+///
+/// # Layouts
+/// Tables and single records can be viewed through a layout declared with
+/// [segment_layout](macro.segment_layout.html), by appending `as Layout` to a
+/// `table(base, stride n, count n)` or a `record(offset)`:
+/// ```rust
+/// # use binseg::{segment_binary, segment_layout};
+/// #
+/// segment_layout! {
+///     pub Word {
+///         value: u16le @ 0
+///     }
+/// }
+///
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         words: table(0x00, stride 2, count 3) as Word,
+///         last_word: record(0x06) as Word
+///     }
+/// }
+///
+/// let mut seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.words(1).value(), 0xefbe);
+/// assert_eq!(seq_bin.last_word().value(), 0xdec0);
+///
+/// seq_bin.last_word_mut().set_value(0x1234);
+/// assert_eq!(&seq_bin.as_bytes()[6..], &[0x34, 0x12]);
+/// ```
+///
+/// The stride of a table is checked at compile time, and has to be at least the size of its
+/// layout:
+/// ```compile_fail
+/// # use binseg::{segment_binary, segment_layout};
+/// #
+/// # segment_layout! {
+/// #     pub Word {
+/// #         value: u16le @ 0
+/// #     }
+/// # }
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         words: table(0x00, stride 1, count 4) as Word
+///     }
+/// }
+/// ```
+///
+/// # Pointers
+/// A segment can start where a 24 bit little endian pointer in another segment points to, with
+/// `ptr24(source) len size` for the first pointer of `source` or `ptr24(source[index]) len size` for
+/// the pointer at `index` of a segment of consecutive pointers. The source has to be declared
+/// before the pointer segment. Pointers are followed once, when the binary is loaded:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         level_pointers: 0x00..0x06,
+///         level_data: ptr24(level_pointers[1]) len 2
+///     }
+/// }
+///
+/// let any_bin = AnyBin::from_slice(&[0x08, 0x00, 0x00, 0x06, 0x00, 0x00, 0xaa, 0xbb]).unwrap();
+/// assert_eq!(any_bin.level_data(), &[0xaa, 0xbb]);
+///
+/// let any_bin = AnyBin::from_slice(&[0x08, 0x00, 0x00, 0x07, 0x00, 0x00, 0xaa, 0xbb]);
+/// assert!(matches!(any_bin, Err(Error::SegmentOutOfBounds { segment: "level_data", .. })));
+/// ```
+/// `ptr24(source[..]) len size` follows every pointer of `source`, and its accessors take the
+/// index of the pointer:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         level_pointers: 0x00..0x06,
+///         level_data: ptr24(level_pointers[..]) len 2
+///     }
+/// }
+///
+/// let bytes = [0x06, 0x00, 0x00, 0x07, 0x00, 0x00, 0xaa, 0xbb, 0xcc];
+/// let mut any_bin = AnyBin::from_slice(&bytes).unwrap();
+/// assert_eq!(any_bin.level_data_len(), 2);
+/// assert_eq!(any_bin.level_data(0), &[0xaa, 0xbb]);
+/// assert_eq!(any_bin.level_data(1), &[0xbb, 0xcc]);
+///
+/// any_bin.level_data_mut(1)[1] = 0xdd;
+/// assert_eq!(&any_bin.as_bytes()[6..], &[0xaa, 0xbb, 0xdd]);
+///
+/// let any_bin = AnyBin::from_slice(&[0x06, 0x00, 0x00, 0x08, 0x00, 0x00, 0xaa, 0xbb]);
+/// assert!(matches!(any_bin, Err(Error::InvalidPointer { segment: "level_data", index: 1, .. })));
+/// ```
+/// With a `#[mapping(...)]`, the pointers are SNES addresses.
+///
+/// # Terminated segments
+/// A segment declared as `terminated(start, terminator)` ends with the first occurrence of the
+/// terminator after `start`, optionally searching at most `max` bytes with
+/// `terminated(start, terminator, max size)`. Its accessors leave the terminator out, except for
+/// the one ending in `_with_terminator`:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         objects: terminated(0x02, b"\xff"),
+///         sprites: terminated(0x02, b"\xff", max 2)
+///     }
+/// }
+///
+/// let any_bin = AnyBin::from_slice(&[0x00, 0x00, 0x12, 0xff, 0x34]).unwrap();
+/// assert_eq!(any_bin.objects(), &[0x12]);
+/// assert_eq!(any_bin.objects_with_terminator(), &[0x12, 0xff]);
+///
+/// let any_bin = AnyBin::from_slice(&[0x00, 0x00, 0x12, 0x34, 0xff]);
+/// assert!(matches!(any_bin, Err(Error::MissingTerminator { segment: "sprites", .. })));
+/// ```
+///
+/// An empty terminator fails to compile:
+/// ```compile_fail
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         objects: terminated(0x02, b"")
+///     }
+/// }
+/// ```
+///
+/// # Compressed segments
+/// Segments marked with `#[compressed(lz2)]` or `#[compressed(lz3)]` hold data compressed in one
+/// of the formats of the [compression](compression/index.html) module. They get an accessor
+/// ending in `_decompressed`, which may stop before the end of the segment, and a setter that
+/// compresses new data into the segment. The setter fills the rest of the segment with zeros, or
+/// with the byte given by `#[compressed(lz2, fill 0xff)]`. If the compressed data is larger than
+/// the segment, the setter returns an error and leaves the segment untouched:
+/// ```rust
+/// # use binseg::{segment_binary, Error};
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         #[compressed(lz2, fill 0xff)]
+///         graphics: 0x00..0x08
+///     }
+/// }
+///
+/// let mut any_bin = AnyBin::from_slice(&[0x01, 0x12, 0x34, 0x22, 0x07, 0xff, 0x00, 0x00]).unwrap();
+/// assert_eq!(any_bin.graphics_decompressed().unwrap(), [0x12, 0x34, 0x07, 0x07, 0x07]);
+///
+/// assert_eq!(any_bin.set_graphics_decompressed(&[0x07; 5]).unwrap(), 3);
+/// assert_eq!(any_bin.graphics(), &[0x24, 0x07, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+///
+/// let result = any_bin.set_graphics_decompressed(&[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde]);
+/// assert!(matches!(result, Err(Error::DoesNotFit { size: 9, available: 8, .. })));
+/// assert_eq!(any_bin.graphics_decompressed().unwrap(), [0x07; 5]);
+/// ```
+///
+/// # SNES addresses
+/// With `#[mapping(...)]`, all segments are declared in addresses on the SNES bus instead of
+/// offsets into the binary. The mapping can be `LoRom`, `HiRom`, `ExLoRom`, `ExHiRom` or `Sa1`,
+/// see [snes::Mapping](snes/enum.Mapping.html). A segment has to map to a contiguous range of the
+/// binary:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     /// A tiny LoRom.
+///     #[mapping(LoRom)]
+///     pub BeefRom("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x008000..0x008004,
+///         best_code: u32le @ 0x808004
+///     }
+/// }
+///
+/// let beef_rom = BeefRom::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(beef_rom.dead_beef(), &[0xde, 0xad, 0xbe, 0xef]);
+/// assert_eq!(beef_rom.best_code(), 0xdec057be);
+/// ```
+///
+/// Segments are checked for overlaps by the bytes of the binary they map to, so mirrored
+/// addresses of the same bytes overlap:
+/// ```compile_fail
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     #[mapping(LoRom)]
+///     pub BeefRom("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x008000..0x008004,
+///         mirrored_beef: 0x808000..0x808004
+///     }
+/// }
+/// ```
+///
+/// # Internal headers
+/// A segment declared as `snes_header` is the internal header of a SNES rom, which is read through
+/// an [snes::InternalHeader](snes/struct.InternalHeader.html). It needs a `#[mapping(...)]` to be
+/// found. The segmenter also gets `verify_checksum` and `fix_checksum`, which check and update the
+/// checksum of the header after the binary was modified:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     #[mapping(LoRom)]
+///     pub AnyRom(len(0x8000)) {
+///         header: snes_header,
+///         first_word: u16le @ 0x008000
+///     }
+/// }
+///
+/// let mut any_rom = AnyRom::from_bytes(vec![0x00; 0x8000]).unwrap();
+/// any_rom.header_mut().set_title(*b"BEEF                 ");
+/// assert_eq!(&any_rom.header().title(), b"BEEF                 ");
+/// assert!(!any_rom.verify_checksum());
+///
+/// any_rom.fix_checksum();
+/// assert!(any_rom.verify_checksum());
+///
+/// any_rom.set_first_word(0x1234);
+/// assert!(!any_rom.verify_checksum());
+/// ```
+///
+/// # Copier headers
+/// Many SNES rom dumps start with a 512 byte copier header. With `#[copier_header]`, such a
+/// header is detected by the length of the binary and stripped before the binary is checked and
+/// segmented, so headered and unheadered dumps load alike. Writing the binary restores the header:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     #[copier_header]
+///     pub AnyRom(len(0x400)) {
+///         first_word: u16le @ 0x00
+///     }
+/// }
+///
+/// let mut dump = vec![0x00; 0x200];
+/// dump.extend_from_slice(&[0x34, 0x12]);
+/// dump.resize(0x600, 0x00);
+///
+/// let mut any_rom = AnyRom::from_slice(&dump).unwrap();
+/// assert_eq!(any_rom.copier_header(), Some(&[0x00; 0x200][..]));
+/// assert_eq!(any_rom.first_word(), 0x1234);
+///
+/// any_rom.set_first_word(0xabcd);
+/// let mut written = Vec::new();
+/// any_rom.write_to(&mut written).unwrap();
+/// assert_eq!(written.len(), 0x600);
+/// assert_eq!(&written[0x200..0x202], &[0xcd, 0xab]);
+///
+/// let any_rom = AnyRom::from_slice(&dump[0x200..]).unwrap();
+/// assert_eq!(any_rom.copier_header(), None);
+/// ```
+///
+/// # Patches
+/// `create_ips` and `create_bps` create an [IPS](ips/index.html) or a [BPS](bps/index.html) patch
+/// from a pristine binary to a modified one. A binary patched with
+/// [bps::apply](bps/fn.apply.html) can be loaded like any other binary. Segmenters can derive
+/// `Debug` and `Clone`, which makes it easy to keep the pristine binary around:
+/// ```rust
+/// use binseg::{bps, segment_binary};
+///
+/// segment_binary! {
+///     #[derive(Debug, Clone)]
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x00..0x04,
+///         best_code: 0x04..0x08
+///     }
+/// }
+///
+/// let original = BeefBin::from_file("test_bins/beef.bin").unwrap();
+/// let mut seq_bin = original.clone();
+/// assert!(format!("{original:?}").starts_with("BeefBin"));
+/// seq_bin.best_code_mut()[1] = 0x00;
+///
+/// let patch = seq_bin.create_ips(&original).unwrap();
+/// assert_eq!(patch, b"PATCH\x00\x00\x05\x00\x01\x00EOF");
+///
+/// let patch = seq_bin.create_bps(&original);
+/// let patched = bps::apply(&patch, original.as_bytes()).unwrap();
+/// assert_eq!(patched, seq_bin.as_bytes());
+/// ```
+///
+/// `from_patched_file` loads a modified binary from the pristine one and an IPS, BPS or UPS patch.
+/// The pristine binary has to match the hash of the segmenter, and the patched one the optional
+/// sha256 hash:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// # segment_binary! {
+/// #     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+/// #         dead_beef: 0x00..0x04,
+/// #         best_code: 0x04..0x08
+/// #     }
+/// # }
+/// #
+/// use binseg::Error;
+///
+/// let patch_path = std::env::temp_dir().join(format!("binseg_beef_{}.ips", std::process::id()));
+/// std::fs::write(&patch_path, b"PATCH\x00\x00\x05\x00\x01\x00EOF").unwrap();
+///
+/// let seq_bin = BeefBin::from_patched_file("test_bins/beef.bin", &patch_path, None).unwrap();
+/// assert_eq!(seq_bin.best_code(), &[0xbe, 0x00, 0xc0, 0xde]);
+///
+/// let hacked_sha256 = seq_bin.sha256();
+/// assert!(BeefBin::from_patched_file("test_bins/beef.bin", &patch_path, Some(&hacked_sha256)).is_ok());
+///
+/// match BeefBin::from_patched_file("test_bins/beef.bin", &patch_path, Some("00")) {
+///     Err(Error::HashMismatch { actual, .. }) => assert_eq!(actual, hacked_sha256),
+///     _ => panic!("the patched binary has a different hash"),
+/// }
+///
+/// std::fs::remove_file(&patch_path).unwrap();
+/// ```
+///
+/// IPS patches have to be made against binaries without copier header. BPS and UPS patches made
+/// against a binary with copier header are detected by their source size:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     #[copier_header]
+///     pub AnyRom(len(0x400)) {
+///         first_word: u16le @ 0x00
+///     }
+/// }
+///
+/// let dump = vec![0x00; 0x600];
+/// let mut patched_dump = dump.clone();
+/// patched_dump[0x200] = 0x34;
+/// patched_dump[0x201] = 0x12;
+///
+/// let dir = std::env::temp_dir();
+/// let base_path = dir.join(format!("binseg_headered_{}.sfc", std::process::id()));
+/// let patch_path = dir.join(format!("binseg_headered_{}.bps", std::process::id()));
+/// std::fs::write(&base_path, &dump).unwrap();
+/// std::fs::write(&patch_path, binseg::bps::create(&dump, &patched_dump)).unwrap();
+///
+/// let any_rom = AnyRom::from_patched_file(&base_path, &patch_path, None).unwrap();
+/// assert_eq!(any_rom.copier_header(), Some(&[0x00; 0x200][..]));
+/// assert_eq!(any_rom.first_word(), 0x1234);
+///
+/// std::fs::remove_file(&base_path).unwrap();
+/// std::fs::remove_file(&patch_path).unwrap();
+/// ```
+///
+/// # Segment hashes
+/// Segments can be given their own hashes with `#[hash(...)]`, using the same syntax as the
+/// hashes of the whole binary, so several hashes can be joined with `+`. `verify_segments`
+/// returns the names of all segments that do not match, which helps checking that a modified
+/// binary left some regions untouched:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub AnyBin {
+///         #[hash(crc32("7c9ca35a") + md5("2f249230a8e7c2bf6005ccd2679259ec"))]
+///         dead_beef: 0x00..0x04,
+///         #[hash(crc32("1f8bf61d"))]
+///         best_code: 0x04..0x08
+///     }
+/// }
+///
+/// let mut any_bin = AnyBin::from_file("test_bins/beef.bin").unwrap();
+/// assert!(any_bin.verify_segments().is_empty());
+///
+/// any_bin.best_code_mut()[0] = 0x00;
+/// assert_eq!(any_bin.verify_segments(), ["best_code"]);
+/// ```
+///
+/// # Overlapping segments
+/// Segments whose ranges are given as literals are checked at compile time. Reversed ranges and
+/// segments overlapping each other are rejected:
+/// ```compile_fail
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x00..0x04,
+///         beef_code: 0x02..0x06
+///     }
+/// }
+/// ```
+/// Segments that are meant to overlap other segments have to be marked with `#[alias]`:
+/// ```rust
+/// # use binseg::segment_binary;
+/// #
+/// segment_binary! {
+///     pub BeefBin("8594c5c15c75fcc5f27893faa4b6a185ec6687306f92b81759d76704319a16b4") {
+///         dead_beef: 0x00..0x04,
+///         /// The beef and the code together
+///         #[alias]
+///         beef_code: 0x02..0x06
+///     }
+/// }
+///
+/// let seq_bin = BeefBin::from_file("test_bins/beef.bin").unwrap();
+///
+/// assert_eq!(seq_bin.beef_code(), &[0xbe, 0xef, 0xbe, 0x57]);
+/// ```
+#[macro_export]
+macro_rules! segment_binary {
+    (#[$($attr:tt)*] $($rest:tt)*) => (
+        $crate::segment_binary!(@outer [] [] [] #[$($attr)*] $($rest)*);
+    );
+    (pub $($rest:tt)*) => (
+        $crate::segment_binary!(@outer [] [] [] pub $($rest)*);
+    );
+
+    // Munches the attributes of the segmenter.
+    (@outer $attrs:tt [] $header:tt #[mapping($mapping:ident)] $($rest:tt)*) => (
+        $crate::segment_binary!(@outer $attrs [$mapping] $header $($rest)*);
+    );
+    (@outer $attrs:tt $mapping:tt [] #[copier_header] $($rest:tt)*) => (
+        $crate::segment_binary!(@outer $attrs $mapping [copier_header] $($rest)*);
+    );
+    (@outer [$($attrs:tt)*] $mapping:tt $header:tt #[$bin_attr:meta] $($rest:tt)*) => (
+        $crate::segment_binary!(@outer [$($attrs)* #[$bin_attr]] $mapping $header $($rest)*);
+    );
+    (
+        @outer $attrs:tt $mapping:tt $header:tt
+        pub $bin_ident:ident ( $($head:tt)* ) {
+            $($body:tt)*
+        }
+    ) => (
+        $crate::segment_binary!(
+            @head [$bin_ident $attrs $mapping $header {$($body)*}] [] () [] $($head)*
+        );
+    );
+    (
+        @outer $attrs:tt $mapping:tt $header:tt
+        pub $bin_ident:ident {
+            $($body:tt)*
+        }
+    ) => (
+        $crate::segment_binary!(
+            @parse [$bin_ident $attrs $mapping $header [{() []}]] [] [] {false [] []} $($body)*
+        );
+    );
+
+    // Munches the revisions of the segmenter. Every revision has an optional name and a list of
+    // checks separated by `+`.
+    (@head $bin:tt [$($revs:tt)*] () [] $rev_ident:ident = $($rest:tt)*) => (
+        $crate::segment_binary!(@head $bin [$($revs)*] ($rev_ident) [] $($rest)*);
+    );
+    (@head $bin:tt [$($revs:tt)*] $name:tt [$($checks:tt)*] $hash_string:literal $($rest:tt)*) => (
+        $crate::segment_binary!(
+            @head_sep $bin [$($revs)*] $name [$($checks)* sha256($hash_string)] $($rest)*
+        );
+    );
+    (
+        @head $bin:tt [$($revs:tt)*] $name:tt [$($checks:tt)*]
+        $check:ident ( $($args:tt)* ) $($rest:tt)*
+    ) => (
+        $crate::segment_binary!(
+            @head_sep $bin [$($revs)*] $name [$($checks)* $check($($args)*)] $($rest)*
+        );
+    );
+    (
+        @head [$bin_ident:ident $attrs:tt $mapping:tt $header:tt {$($body:tt)*}]
+        [$($revs:tt)+] () []
+    ) => (
+        $crate::segment_binary!(
+            @parse [$bin_ident $attrs $mapping $header [$($revs)+]] [] [] {false [] []} $($body)*
+        );
+    );
+    (@head_sep $bin:tt [$($revs:tt)*] $name:tt [$($checks:tt)*] + $($rest:tt)*) => (
+        $crate::segment_binary!(@head $bin [$($revs)*] $name [$($checks)*] $($rest)*);
+    );
+    (@head_sep $bin:tt [$($revs:tt)*] $name:tt [$($checks:tt)*] , $($rest:tt)*) => (
+        $crate::segment_binary!(@head $bin [$($revs)* {$name [$($checks)*]}] () [] $($rest)*);
+    );
+    (@head_sep $bin:tt [$($revs:tt)*] $name:tt [$($checks:tt)*]) => (
+        $crate::segment_binary!(@head $bin [$($revs)* {$name [$($checks)*]}] () []);
+    );
+
+    (@check sha256($hash_string:literal)) => (
+        $crate::Check::Digest($crate::HashAlgorithm::Sha256, $hash_string)
+    );
+    (@check sha1($hash_string:literal)) => (
+        $crate::Check::Digest($crate::HashAlgorithm::Sha1, $hash_string)
+    );
+    (@check md5($hash_string:literal)) => (
+        $crate::Check::Digest($crate::HashAlgorithm::Md5, $hash_string)
+    );
+    (@check crc32($hash_string:literal)) => (
+        $crate::Check::Digest($crate::HashAlgorithm::Crc32, $hash_string)
+    );
+    (@check len($len:expr)) => ($crate::Check::Len($len));
+    (@check magic($offset:expr, $magic:expr)) => ($crate::Check::Magic($offset, $magic));
+    (@check validate($validator:path)) => (
+        $crate::Check::Validate(stringify!($validator), $validator)
+    );
+    (@check $check:ident $args:tt) => (
+        compile_error!(concat!("unknown check `", stringify!($check $args), "`"))
+    );
+
+    (@digest sha256($hash_string:literal)) => (($crate::HashAlgorithm::Sha256, $hash_string));
+    (@digest sha1($hash_string:literal)) => (($crate::HashAlgorithm::Sha1, $hash_string));
+    (@digest md5($hash_string:literal)) => (($crate::HashAlgorithm::Md5, $hash_string));
+    (@digest crc32($hash_string:literal)) => (($crate::HashAlgorithm::Crc32, $hash_string));
+    (@digest $algorithm:ident $args:tt) => (
+        compile_error!(concat!("unknown hash `", stringify!($algorithm $args), "`"))
+    );
+
+    // Munches the segment definitions one by one, collecting the attributes and options of the
+    // next segment. The options are `{alias [hashes] [compression]}`.
+    (@parse $head:tt $segs:tt $meta:tt {$alias:tt $hashes:tt $compression:tt} #[alias] $($rest:tt)*) => (
+        $crate::segment_binary!(@parse $head $segs $meta {true $hashes $compression} $($rest)*);
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt {$alias:tt [$($hash:tt)*] $compression:tt}
+        #[hash($hash_string:literal $(+ $($more:tt)+)?)] $($rest:tt)*
+    ) => (
+        $crate::segment_binary!(
+            @parse $head $segs $meta {$alias [$($hash)* sha256($hash_string)] $compression}
+            $(#[hash($($more)+)])? $($rest)*
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt {$alias:tt [$($hash:tt)*] $compression:tt}
+        #[hash($algorithm:ident($hash_string:literal) $(+ $($more:tt)+)?)] $($rest:tt)*
+    ) => (
+        $crate::segment_binary!(
+            @parse $head $segs $meta {$alias [$($hash)* $algorithm($hash_string)] $compression}
+            $(#[hash($($more)+)])? $($rest)*
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt {$alias:tt $hashes:tt []}
+        #[compressed($format:ident $(, fill $fill:expr)?)] $($rest:tt)*
+    ) => (
+        $crate::segment_binary!(
+            @parse $head $segs $meta {$alias $hashes [$format [$($fill)?]]} $($rest)*
+        );
+    );
+    (@parse $head:tt $segs:tt [$($meta:tt)*] $opts:tt #[$meta_attr:meta] $($rest:tt)*) => (
+        $crate::segment_binary!(@parse $head $segs [$($meta)* #[$meta_attr]] $opts $($rest)*);
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : table($base:literal, stride $stride:literal, count $count:literal)
+        as $layout:ident $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (table_as $layout lit $base, $stride, $count)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : table($base:expr, stride $stride:expr, count $count:expr)
+        as $layout:ident $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (table_as $layout expr $base, $stride, $count)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse [$bin_ident:ident $attrs:tt [] $header:tt $revs:tt] $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : snes_header $($rest:tt)*
+    ) => (
+        compile_error!(concat!(
+            "`", stringify!($seg_ident), "` needs a `#[mapping(...)]` to find the internal header"
+        ));
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : snes_header $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(@push $head $segs {$seg_ident $meta $opts (snes_header)} $($($rest)*)?);
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : ptr24($source:ident[..]) len $len:expr $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (ptr24_table $source $len)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : ptr24($source:ident $([$index:expr])?) len $len:expr $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (ptr24 $source [$($index)?] $len)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : terminated($start:expr, $terminator:expr $(, max $max:expr)?)
+        $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (terminated $start, $terminator, [$($max)?])}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : record($offset:literal) as $layout:ident $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (record_as $layout lit $offset)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : record($offset:expr) as $layout:ident $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (record_as $layout expr $offset)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : table($base:literal, stride $stride:literal, count $count:literal)
+        $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (table lit $base, $stride, $count)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : table($base:expr, stride $stride:expr, count $count:expr)
+        $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (table expr $base, $stride, $count)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : $ty:tt @ $offset:literal $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (typed $ty lit $offset)} $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : $ty:tt @ $offset:expr $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (typed $ty expr $offset)} $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : { $($rev_ident:ident : $mem_range:expr),+ $(,)? } $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (revs $(($rev_ident $mem_range))+)}
+            $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : $start:literal .. $end:literal $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (lit $start $end)} $($($rest)*)?
+        );
+    );
+    (
+        @parse $head:tt $segs:tt $meta:tt $opts:tt
+        $seg_ident:ident : $mem_range:expr $(, $($rest:tt)*)?
+    ) => (
+        $crate::segment_binary!(
+            @push $head $segs {$seg_ident $meta $opts (expr $mem_range)} $($($rest)*)?
+        );
+    );
+    (@parse $head:tt [$($segs:tt)*] [] {false [] []}) => (
+        $crate::segment_binary!(@emit $head $($segs)*);
+    );
+    (@push $head:tt [$($segs:tt)*] $seg:tt $($rest:tt)*) => (
+        $crate::segment_binary!(@parse $head [$($segs)* $seg] [] {false [] []} $($rest)*);
+    );
+
+    (@revision_doc () []) => ("- any binary");
+    (@revision_doc ($($rev_ident:ident)?) [$($check:ident $args:tt)+]) => (
+        concat!("- ", $(stringify!($rev_ident), ": ",)? "`", stringify!($($check $args)++), "`")
+    );
+
+    (@mapping) => (None);
+    (@mapping $mapping:ident) => (Some($crate::snes::Mapping::$mapping));
+
+    (@copier_header $bin_data:ident) => ((None, $bin_data));
+    (@copier_header $bin_data:ident copier_header) => ($crate::snes::strip_copier_header($bin_data));
+
+    (@index) => (0);
+    (@index $index:expr) => ($index);
+
+    // The type of the resolved range of a segment, and its bytes for hashing.
+    (@range_type ptr24_table $($mem_range:tt)*) => (Vec<std::ops::Range<usize>>);
+    (@range_type $($mem_range:tt)*) => (std::ops::Range<usize>);
+    (@hashed $bin_data:expr, $range:expr, ptr24_table $($mem_range:tt)*) => (
+        &$range.iter().flat_map(|range| $bin_data[range.clone()].iter().copied()).collect::<Vec<u8>>()
+    );
+    (@hashed $bin_data:expr, $range:expr, $($mem_range:tt)*) => (&$bin_data[$range.clone()]);
+
+    (@max) => (None);
+    (@max $max:expr) => (Some($max));
+
+    (@revision_name) => (None);
+    (@revision_name $rev_ident:ident) => (Some(stringify!($rev_ident)));
+
+    // Resolves the range of a segment for the loaded binary.
+    (@resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident lit $start:literal $end:literal) => (
+        $crate::resolve_range(stringify!($seg_ident), $start..$end, $mapping, $bin_data.len())
+    );
+    (@resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident expr $mem_range:expr) => (
+        $crate::resolve_range(stringify!($seg_ident), $mem_range, $mapping, $bin_data.len())
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        revs $(($rev_ident:ident $mem_range:expr))+
+    ) => (
+        match $revision {
+            $(Some(stringify!($rev_ident)) => {
+                $crate::resolve_range(stringify!($seg_ident), $mem_range, $mapping, $bin_data.len())
+            })+
+            revision => Err($crate::Error::NoRangeForRevision { segment: stringify!($seg_ident), revision }),
+        }
+    );
+
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        typed $ty:tt $offset_kind:ident $offset:expr
+    ) => (
+        $crate::resolve_range(
+            stringify!($seg_ident),
+            ($offset)..($offset) + <$crate::segment_binary!(@codec $ty) as $crate::Codec>::SIZE,
+            $mapping, $bin_data.len(),
+        )
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        table $base_kind:ident $base:expr, $stride:expr, $count:expr
+    ) => (
+        $crate::resolve_table(stringify!($seg_ident), $base, $stride, $count, $mapping, $bin_data.len())
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        table_as $layout:ident $base_kind:ident $base:expr, $stride:expr, $count:expr
+    ) => (
+        $crate::resolve_table(stringify!($seg_ident), $base, $stride, $count, $mapping, $bin_data.len())
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        record_as $layout:ident $offset_kind:ident $offset:expr
+    ) => (
+        $crate::resolve_range(
+            stringify!($seg_ident),
+            ($offset)..($offset) + $layout::SIZE,
+            $mapping, $bin_data.len(),
+        )
+    );
+    (@resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident snes_header) => (
+        $crate::resolve_range(
+            stringify!($seg_ident),
+            $crate::snes::HEADER_ADDRESS as usize..$crate::snes::HEADER_ADDRESS as usize + $crate::snes::InternalHeader::SIZE,
+            $mapping, $bin_data.len(),
+        )
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        ptr24 $source:ident [$($index:expr)?] $len:expr
+    ) => (
+        $crate::resolve_pointer(
+            stringify!($seg_ident),
+            (stringify!($source), &$source),
+            $crate::segment_binary!(@index $($index)?),
+            $len, $mapping, &$bin_data,
+        )
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        ptr24_table $source:ident $len:expr
+    ) => (
+        $crate::resolve_pointer_table(stringify!($seg_ident), &$source, $len, $mapping, &$bin_data)
+    );
+    (
+        @resolve $seg_ident:ident $revision:ident $mapping:ident $bin_data:ident
+        terminated $start:expr, $terminator:expr, [$($max:expr)?]
+    ) => (
+        $crate::resolve_terminated(
+            stringify!($seg_ident),
+            $start,
+            $terminator,
+            $crate::segment_binary!(@max $($max)?),
+            $mapping, &$bin_data,
+        )
+    );
+
+    // Maps the type of a typed segment to its codec.
+    (@codec [$ty:tt; $len:expr]) => ([$crate::segment_binary!(@codec $ty); $len]);
+    (@codec u16le) => ($crate::codec::u16le);
+    (@codec u16be) => ($crate::codec::u16be);
+    (@codec u24le) => ($crate::codec::u24le);
+    (@codec u24be) => ($crate::codec::u24be);
+    (@codec u32le) => ($crate::codec::u32le);
+    (@codec u32be) => ($crate::codec::u32be);
+    (@codec u64le) => ($crate::codec::u64le);
+    (@codec u64be) => ($crate::codec::u64be);
+    (@codec i16le) => ($crate::codec::i16le);
+    (@codec i16be) => ($crate::codec::i16be);
+    (@codec i32le) => ($crate::codec::i32le);
+    (@codec i32be) => ($crate::codec::i32be);
+    (@codec i64le) => ($crate::codec::i64le);
+    (@codec i64be) => ($crate::codec::i64be);
+    (@codec $ty:ty) => ($ty);
+
+    (@literal $seg_ident:ident $alias:tt typed $ty:tt lit $offset:literal) => (
+        $crate::segment_binary!(
+            @literal $seg_ident $alias
+            lit $offset ($offset + <$crate::segment_binary!(@codec $ty) as $crate::Codec>::SIZE)
+        )
+    );
+    (
+        @literal $seg_ident:ident $alias:tt
+        table lit $base:literal, $stride:literal, $count:literal
+    ) => (
+        $crate::segment_binary!(@literal $seg_ident $alias lit $base ($base + $stride * $count))
+    );
+    (
+        @literal $seg_ident:ident $alias:tt
+        table_as $layout:ident lit $base:literal, $stride:literal, $count:literal
+    ) => (
+        $crate::segment_binary!(@literal $seg_ident $alias lit $base ($base + $stride * $count))
+    );
+    (@literal $seg_ident:ident $alias:tt record_as $layout:ident lit $offset:literal) => (
+        $crate::segment_binary!(@literal $seg_ident $alias lit $offset ($offset + $layout::SIZE))
+    );
+    (@literal $seg_ident:ident $alias:tt lit $start:literal $end:tt) => (
+        Some($crate::LiteralSegment {
+            start: $start,
+            end: $end,
+            alias: $alias,
+            reversed_msg: concat!("segment `", stringify!($seg_ident), "` has a reversed range"),
+            overlap_msg: concat!(
+                "segment `",
+                stringify!($seg_ident),
+                "` overlaps another segment, mark it with #[alias] if this is intended"
+            ),
+        })
+    );
+    (@literal $seg_ident:ident $alias:tt $($mem_range:tt)*) => (None);
+
+    // Checks the declaration of a segment at compile time: the stride of a table, the revision
+    // names of per-revision ranges and the terminator of a terminated segment.
+    (@static $bin_ident:ident $seg_ident:ident table $base_kind:ident $base:expr, $stride:expr, $count:expr) => (
+        const _: () = assert!(
+            $stride != 0,
+            concat!("table `", stringify!($seg_ident), "` has a stride of zero"),
+        );
+    );
+    (
+        @static $bin_ident:ident $seg_ident:ident
+        table_as $layout:ident $base_kind:ident $base:expr, $stride:expr, $count:expr
+    ) => (
+        $crate::segment_binary!(@static $bin_ident $seg_ident table $base_kind $base, $stride, $count);
+        const _: () = assert!(
+            $stride >= $layout::SIZE,
+            concat!("the records of `", stringify!($seg_ident), "` are smaller than `", stringify!($layout), "`"),
+        );
+    );
+    (@static $bin_ident:ident $seg_ident:ident revs $(($rev_ident:ident $mem_range:expr))+) => (
+        $(const _: () = assert!(
+            $crate::has_revision($bin_ident::REVISIONS, stringify!($rev_ident)),
+            concat!("segment `", stringify!($seg_ident), "` has a range for the unknown revision `", stringify!($rev_ident), "`"),
+        );)+
+    );
+    (@static $bin_ident:ident $seg_ident:ident terminated $start:expr, $terminator:expr, $max:tt) => (
+        const _: () = assert!(
+            !$terminator.is_empty(),
+            concat!("segment `", stringify!($seg_ident), "` has an empty terminator"),
+        );
+    );
+    (@static $bin_ident:ident $seg_ident:ident $($mem_range:tt)*) => ();
+
+    // Generates the accessors of a segment.
+    (@accessors $seg_ident:ident [$($meta_attr:tt)*] typed $ty:tt $($offset:tt)*) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            pub fn $seg_ident(&self) -> <$crate::segment_binary!(@codec $ty) as $crate::Codec>::Value {
+                <$crate::segment_binary!(@codec $ty) as $crate::Codec>::decode(
+                    &self.bin_data[self.segments.$seg_ident.clone()],
+                )
+            }
+
+            #[doc = "Sets the value of the segment `" $seg_ident "`."]
+            pub fn [<set_ $seg_ident>](
+                &mut self,
+                value: <$crate::segment_binary!(@codec $ty) as $crate::Codec>::Value,
+            ) {
+                <$crate::segment_binary!(@codec $ty) as $crate::Codec>::encode(
+                    value,
+                    &mut self.bin_data[self.segments.$seg_ident.clone()],
+                )
+            }
+        }
+    );
+    (
+        @accessors $seg_ident:ident [$($meta_attr:tt)*]
+        table $base_kind:ident $base:expr, $stride:expr, $count:expr
+    ) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn $seg_ident(&self, index: usize) -> &[u8] {
+                assert!(index < $count, concat!("index out of bounds for `", stringify!($seg_ident), "`"));
+                let start = self.segments.$seg_ident.start + index * $stride;
+
+                &self.bin_data[start..start + $stride]
+            }
+
+            #[doc = "Mutable access to a record of the table `" $seg_ident "`."]
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn [<$seg_ident _mut>](&mut self, index: usize) -> &mut [u8] {
+                assert!(index < $count, concat!("index out of bounds for `", stringify!($seg_ident), "`"));
+                let start = self.segments.$seg_ident.start + index * $stride;
+
+                &mut self.bin_data[start..start + $stride]
+            }
+
+            #[doc = "Returns the number of records in the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _len>](&self) -> usize {
+                $count
+            }
+
+            #[doc = "Returns an iterator over the records of the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _iter>](&self) -> std::slice::ChunksExact<'_, u8> {
+                self.bin_data[self.segments.$seg_ident.clone()].chunks_exact($stride)
+            }
+
+            #[doc = "Returns an iterator over mutable records of the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _iter_mut>](&mut self) -> std::slice::ChunksExactMut<'_, u8> {
+                self.bin_data[self.segments.$seg_ident.clone()].chunks_exact_mut($stride)
+            }
+        }
+    );
+    (
+        @accessors $seg_ident:ident [$($meta_attr:tt)*]
+        table_as $layout:ident $base_kind:ident $base:expr, $stride:expr, $count:expr
+    ) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn $seg_ident(&self, index: usize) -> $layout<'_> {
+                assert!(index < $count, concat!("index out of bounds for `", stringify!($seg_ident), "`"));
+                let start = self.segments.$seg_ident.start + index * $stride;
+
+                $layout::new(&self.bin_data[start..start + $stride])
+            }
+
+            #[doc = "Mutable access to a record of the table `" $seg_ident "`."]
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn [<$seg_ident _mut>](&mut self, index: usize) -> [<$layout Mut>]<'_> {
+                assert!(index < $count, concat!("index out of bounds for `", stringify!($seg_ident), "`"));
+                let start = self.segments.$seg_ident.start + index * $stride;
+
+                [<$layout Mut>]::new(&mut self.bin_data[start..start + $stride])
+            }
+
+            #[doc = "Returns the number of records in the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _len>](&self) -> usize {
+                $count
+            }
+
+            #[doc = "Returns an iterator over the records of the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _iter>](&self) -> impl Iterator<Item = $layout<'_>> + '_ {
+                self.bin_data[self.segments.$seg_ident.clone()].chunks_exact($stride).map($layout::new)
+            }
+
+            #[doc = "Returns an iterator over mutable records of the table `" $seg_ident "`."]
+            pub fn [<$seg_ident _iter_mut>](&mut self) -> impl Iterator<Item = [<$layout Mut>]<'_>> + '_ {
+                self.bin_data[self.segments.$seg_ident.clone()]
+                    .chunks_exact_mut($stride)
+                    .map([<$layout Mut>]::new)
+            }
+        }
+    );
+    (
+        @accessors $seg_ident:ident [$($meta_attr:tt)*]
+        record_as $layout:ident $offset_kind:ident $offset:expr
+    ) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            pub fn $seg_ident(&self) -> $layout<'_> {
+                $layout::new(&self.bin_data[self.segments.$seg_ident.clone()])
+            }
+
+            #[doc = "Mutable access to the record `" $seg_ident "`."]
+            pub fn [<$seg_ident _mut>](&mut self) -> [<$layout Mut>]<'_> {
+                [<$layout Mut>]::new(&mut self.bin_data[self.segments.$seg_ident.clone()])
+            }
+        }
+    );
+    (@accessors $seg_ident:ident [$($meta_attr:tt)*] snes_header) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            pub fn $seg_ident(&self) -> $crate::snes::InternalHeader<'_> {
+                $crate::snes::InternalHeader::new(&self.bin_data[self.segments.$seg_ident.clone()])
+            }
+
+            #[doc = "Mutable access to the internal header `" $seg_ident "`."]
+            pub fn [<$seg_ident _mut>](&mut self) -> $crate::snes::InternalHeaderMut<'_> {
+                $crate::snes::InternalHeaderMut::new(&mut self.bin_data[self.segments.$seg_ident.clone()])
+            }
+
+            /// Returns whether the checksum in the internal header matches the binary, including
+            /// all modifications.
+            pub fn verify_checksum(&self) -> bool {
+                let header = self.$seg_ident();
+
+                header.checksum() ^ header.checksum_complement() == 0xffff
+                    && header.checksum() == $crate::snes::checksum(&self.bin_data)
+            }
+
+            /// Updates the checksum in the internal header to match the binary, including all
+            /// modifications.
+            pub fn fix_checksum(&mut self) {
+                let mut header = self.[<$seg_ident _mut>]();
+                header.set_checksum(0x0000);
+                header.set_checksum_complement(0xffff);
+
+                let checksum = $crate::snes::checksum(&self.bin_data);
+                let mut header = self.[<$seg_ident _mut>]();
+                header.set_checksum(checksum);
+                header.set_checksum_complement(!checksum);
+            }
+        }
+    );
+    (@accessors $seg_ident:ident [$($meta_attr:tt)*] ptr24_table $source:ident $len:expr) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn $seg_ident(&self, index: usize) -> &[u8] {
+                &self.bin_data[self.segments.$seg_ident[index].clone()]
+            }
+
+            #[doc = "Mutable access to the target of a pointer of the segment `" $seg_ident "`."]
+            ///
+            /// # Panics
+            /// Panics if `index` is out of bounds.
+            pub fn [<$seg_ident _mut>](&mut self, index: usize) -> &mut [u8] {
+                &mut self.bin_data[self.segments.$seg_ident[index].clone()]
+            }
+
+            #[doc = "Returns the number of pointers of the segment `" $seg_ident "`."]
+            pub fn [<$seg_ident _len>](&self) -> usize {
+                self.segments.$seg_ident.len()
+            }
+        }
+    );
+    (
+        @accessors $seg_ident:ident [$($meta_attr:tt)*]
+        terminated $start:expr, $terminator:expr, $max:tt
+    ) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            pub fn $seg_ident(&self) -> &[u8] {
+                let range = self.segments.$seg_ident.clone();
+
+                &self.bin_data[range.start..range.end - $terminator.len()]
+            }
+
+            #[doc = "Mutable access to the segment `" $seg_ident "`, without the terminator."]
+            pub fn [<$seg_ident _mut>](&mut self) -> &mut [u8] {
+                let range = self.segments.$seg_ident.clone();
+
+                &mut self.bin_data[range.start..range.end - $terminator.len()]
+            }
+
+            #[doc = "Returns the segment `" $seg_ident "`, including the terminator."]
+            pub fn [<$seg_ident _with_terminator>](&self) -> &[u8] {
+                &self.bin_data[self.segments.$seg_ident.clone()]
+            }
+        }
+    );
+    // Adds the accessors of compressed segments.
+    (@compressed $seg_ident:ident) => ();
+    (@compressed $seg_ident:ident $format:ident [$($fill:expr)?]) => (
+        $crate::paste::paste! {
+            #[doc = "Decompresses the segment `" $seg_ident "`."]
+            pub fn [<$seg_ident _decompressed>](&self) -> Result<Vec<u8>, $crate::Error> {
+                $crate::compression::decompress(
+                    $crate::segment_binary!(@format $format),
+                    &self.bin_data[self.segments.$seg_ident.clone()],
+                )
+            }
+
+            #[doc = "Compresses `data` into the segment `" $seg_ident "` and fills the rest of the"]
+            #[doc = "segment. Returns the size of the compressed data, or an error if it does not fit."]
+            pub fn [<set_ $seg_ident _decompressed>](&mut self, data: &[u8]) -> Result<usize, $crate::Error> {
+                $crate::compression::compress_into(
+                    stringify!($seg_ident),
+                    $crate::segment_binary!(@format $format),
+                    data,
+                    &mut self.bin_data[self.segments.$seg_ident.clone()],
+                    $crate::segment_binary!(@fill $($fill)?),
+                )
+            }
+        }
+    );
+    (@fill) => (0x00);
+    (@fill $fill:expr) => ($fill);
+    (@format lz2) => ($crate::compression::Format::Lz2);
+    (@format lz3) => ($crate::compression::Format::Lz3);
+    (@format $format:ident) => (
+        compile_error!(concat!("unknown compression format `", stringify!($format), "`"))
+    );
+
+    (@accessors $seg_ident:ident [$($meta_attr:tt)*] $($mem_range:tt)*) => (
+        $crate::paste::paste! {
+            $($meta_attr)*
+            pub fn $seg_ident(&self) -> &[u8] {
+                &self.bin_data[self.segments.$seg_ident.clone()]
+            }
+
+            #[doc = "Mutable access to the segment `" $seg_ident "`."]
+            pub fn [<$seg_ident _mut>](&mut self) -> &mut [u8] {
+                &mut self.bin_data[self.segments.$seg_ident.clone()]
+            }
+        }
+    );
+
+    (
+        @emit [
+            $bin_ident:ident [$($bin_attr:tt)*] [$($mapping:ident)?] [$($header:ident)?]
+            [$({($($rev_ident:ident)?) [$($check:ident $args:tt)*]})+]
+        ]
+        $({
+            $seg_ident:ident [$($meta_attr:tt)*]
+            {$alias:tt [$($algorithm:ident $digest:tt)*] [$($format:ident $fill:tt)?]}
+            ($($mem_range:tt)*)
+        })*
+    ) => (
+        const _: () = $crate::check_literal_segments(
+            &[$($crate::segment_binary!(@literal $seg_ident $alias $($mem_range)*)),*],
+            $crate::segment_binary!(@mapping $($mapping)?),
+        );
+        $($crate::segment_binary!(@static $bin_ident $seg_ident $($mem_range)*);)*
+
+        $crate::paste::paste! {
+            $($bin_attr)*
+            pub struct $bin_ident {
+                bin_data: Vec<u8>,
+                copier_header: Option<Vec<u8>>,
+                revision: Option<usize>,
+                segments: [<$bin_ident Segments>],
+            }
+
+            #[derive(Debug, Clone)]
+            struct [<$bin_ident Segments>] {
+                $($seg_ident: $crate::segment_binary!(@range_type $($mem_range)*)),*
+            }
+
+            impl $bin_ident {
+                /// Creates a new segmentation for the binary at `path`, which has to match one of the
+                /// following revisions:
+                $(#[doc = $crate::segment_binary!(@revision_doc ($($rev_ident)?) [$($check $args)*])])+
+                pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<$bin_ident, $crate::Error> {
+                    let bin_data = std::fs::read(path)?;
+
+                    $bin_ident::from_bytes(bin_data)
+                }
+
+                /// Creates a new segmentation for the binary at `path` without checking its hash.
+                pub fn from_file_unchecked<P: AsRef<std::path::Path>>(path: P) -> Result<$bin_ident, $crate::Error> {
+                    let bin_data = std::fs::read(path)?;
+                    let (copier_header, bin_data) = $crate::segment_binary!(@copier_header bin_data $($header)?);
+                    let revision = $crate::identify(&bin_data, $bin_ident::REVISIONS).ok();
+
+                    $bin_ident::new(bin_data, copier_header, revision)
+                }
+
+                /// Creates a new segmentation for the binary at `base_path` with the IPS, BPS or UPS
+                /// patch at `patch_path` applied. The base binary has to match one of the revisions,
+                /// whose segments are used for the patched binary. If `patched_sha256` is given, the
+                /// patched binary has to have that sha256 hash.
+                ///
+                /// IPS patches have to be made against the binary without copier header. BPS and UPS
+                /// patches made against the binary with its copier header are detected by their
+                /// source size.
+                pub fn from_patched_file<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+                    base_path: P,
+                    patch_path: Q,
+                    patched_sha256: Option<&str>,
+                ) -> Result<$bin_ident, $crate::Error> {
+                    let base = $bin_ident::from_file(base_path)?;
+                    let patch = std::fs::read(patch_path)?;
+                    let (copier_header, bin_data) =
+                        $crate::apply_patch_to_stripped(&patch, base.copier_header(), &base.bin_data)?;
+
+                    if let Some(expected) = patched_sha256 {
+                        let actual = $crate::HashAlgorithm::Sha256.hex_digest(&bin_data);
+                        if !expected.eq_ignore_ascii_case(&actual) {
+                            return Err($crate::Error::HashMismatch {
+                                algorithm: $crate::HashAlgorithm::Sha256,
+                                expected: String::from(expected),
+                                actual,
+                            });
+                        }
+                    }
+
+                    $bin_ident::new(bin_data, copier_header, base.revision)
+                }
+
+                /// Creates a new segmentation for the binary read from `reader` until EOF.
+                pub fn from_reader<R: std::io::Read>(mut reader: R) -> Result<$bin_ident, $crate::Error> {
+                    let mut bin_data = Vec::new();
+                    reader.read_to_end(&mut bin_data)?;
+
+                    $bin_ident::from_bytes(bin_data)
+                }
+
+                /// Creates a new segmentation for a copy of the given binary.
+                pub fn from_slice(bin_data: &[u8]) -> Result<$bin_ident, $crate::Error> {
+                    $bin_ident::from_bytes(bin_data.to_vec())
+                }
+
+                /// Creates a new segmentation for the given binary, taking ownership of it.
+                pub fn from_bytes(bin_data: Vec<u8>) -> Result<$bin_ident, $crate::Error> {
+                    let (copier_header, bin_data) = $crate::segment_binary!(@copier_header bin_data $($header)?);
+                    let revision = $crate::identify(&bin_data, $bin_ident::REVISIONS)?;
+
+                    $bin_ident::new(bin_data, copier_header, Some(revision))
+                }
+
+                const REVISIONS: &[$crate::Revision] = &[$(
+                    $crate::Revision {
+                        name: $crate::segment_binary!(@revision_name $($rev_ident)?),
+                        checks: &[$($crate::segment_binary!(@check $check $args)),*],
+                    }
+                ),+];
+
+                fn new(
+                    bin_data: Vec<u8>,
+                    copier_header: Option<Vec<u8>>,
+                    revision: Option<usize>,
+                ) -> Result<$bin_ident, $crate::Error> {
+                    let revision_name = revision.and_then(|revision| $bin_ident::REVISIONS[revision].name);
+                    let mapping: Option<$crate::snes::Mapping> = $crate::segment_binary!(@mapping $($mapping)?);
+
+                    $(
+                        let $seg_ident = $crate::segment_binary!(
+                            @resolve $seg_ident revision_name mapping bin_data $($mem_range)*
+                        )?;
+                    )*
+                    let segments = [<$bin_ident Segments>] { $($seg_ident),* };
+
+                    Ok($bin_ident { bin_data, copier_header, revision, segments })
+                }
+
+                /// Returns the name of the revision the binary matched when it was loaded, if the
+                /// revision has a name.
+                pub fn revision(&self) -> Option<&'static str> {
+                    self.revision.and_then(|revision| $bin_ident::REVISIONS[revision].name)
+                }
+
+                /// Returns the names of all segments whose contents do not match the hashes they
+                /// were declared with.
+                pub fn verify_segments(&self) -> Vec<&'static str> {
+                    let mut mismatches = Vec::new();
+
+                    $(
+                        let digests = [$($crate::segment_binary!(@digest $algorithm $digest)),*];
+                        let bytes = $crate::segment_binary!(
+                            @hashed self.bin_data, self.segments.$seg_ident, $($mem_range)*
+                        );
+                        if !$crate::verify_digests(bytes, &digests) {
+                            mismatches.push(stringify!($seg_ident));
+                        }
+                    )*
+
+                    mismatches
+                }
+
+                /// Returns the whole binary, including all modifications. A copier header stripped
+                /// when loading the binary is not included.
+                pub fn as_bytes(&self) -> &[u8] {
+                    &self.bin_data
+                }
+
+                /// Returns the copier header stripped when loading the binary, if it had one.
+                pub fn copier_header(&self) -> Option<&[u8]> {
+                    self.copier_header.as_deref()
+                }
+
+                /// Returns the sha256 hash of the binary, including all modifications.
+                pub fn sha256(&self) -> String {
+                    use $crate::crypto_hash::{Algorithm, hex_digest};
+
+                    hex_digest(Algorithm::SHA256, &self.bin_data)
+                }
+
+                /// Creates an IPS patch that turns `original` into this binary, including all
+                /// modifications. Copier headers are not part of the patch.
+                pub fn create_ips(&self, original: &$bin_ident) -> Result<Vec<u8>, $crate::Error> {
+                    $crate::ips::create(&original.bin_data, &self.bin_data)
+                }
+
+                /// Creates a BPS patch that turns `original` into this binary, including all
+                /// modifications. Copier headers are not part of the patch.
+                pub fn create_bps(&self, original: &$bin_ident) -> Vec<u8> {
+                    $crate::bps::create(&original.bin_data, &self.bin_data)
+                }
+
+                /// Writes the whole binary, including all modifications, to `writer`. A copier header
+                /// stripped when loading the binary is written in front of it.
+                pub fn write_to<W: std::io::Write>(&self, mut writer: W) -> Result<(), $crate::Error> {
+                    if let Some(copier_header) = &self.copier_header {
+                        writer.write_all(copier_header)?;
+                    }
+                    writer.write_all(&self.bin_data)?;
+
+                    Ok(())
+                }
+
+                /// Writes the whole binary, including all modifications, to the file at `path`. A
+                /// copier header stripped when loading the binary is written in front of it.
+                pub fn write_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), $crate::Error> {
+                    self.write_to(std::fs::File::create(path)?)
+                }
+
+                $(
+                    $crate::segment_binary!(@accessors $seg_ident [$($meta_attr)*] $($mem_range)*);
+                    $crate::segment_binary!(@compressed $seg_ident $($format $fill)?);
+                )*
+            }
+        }
+    );
+}
+
+/// Declares the layout of a record, like an entry of a table.
+///
+/// The macro creates a read-only view `Name<'a>` over a `&'a [u8]` and a writable view
+/// `NameMut<'a>` over a `&'a mut [u8]`. Every field is declared with a type and an offset into the
+/// record, using the same types as the typed segments of
+/// [segment_binary](macro.segment_binary.html). Both views get a getter per field, and `NameMut`
+/// also gets a `set_` method per field.
+///
+/// # Examples
+/// ```rust
+/// use binseg::segment_layout;
+///
+/// segment_layout! {
+///     /// The header of a level.
+///     pub LevelHeader {
+///         /// The tileset of the level.
+///         tileset: u8 @ 0,
+///         timer: u16be @ 1,
+///         palettes: [u8; 2] @ 3
+///     }
+/// }
+///
+/// assert_eq!(LevelHeader::SIZE, 5);
+///
+/// let mut bytes = [0x07, 0x01, 0x2c, 0x02, 0x05];
+///
+/// let header = LevelHeader::new(&bytes);
+/// assert_eq!(header.tileset(), 7);
+/// assert_eq!(header.timer(), 300);
+/// assert_eq!(header.palettes(), [2, 5]);
+///
+/// let mut header = LevelHeaderMut::new(&mut bytes);
+/// header.set_timer(400);
+/// assert_eq!(bytes, [0x07, 0x01, 0x90, 0x02, 0x05]);
+/// ```
+///
+/// # Bitfields
+/// Fields can cover a part of an integer with `bits lo..hi`, where bit `lo` is included and bit
+/// `hi` is not, or a single bit with `bit n`. Single bits are read as `bool`. The setters only
+/// change the covered bits and drop the bits of the new value that do not fit:
+/// ```rust
+/// use binseg::segment_layout;
+///
+/// segment_layout! {
+///     /// The properties of a sprite tile, `YXPPCCCT`.
+///     pub Properties {
+///         page: u8 @ 0 bit 0,
+///         palette: u8 @ 0 bits 1..4,
+///         priority: u8 @ 0 bits 4..6,
+///         flip_x: u8 @ 0 bit 6,
+///         flip_y: u8 @ 0 bit 7
+///     }
+/// }
+///
+/// let mut bytes = [0b0110_1011];
+///
+/// let properties = Properties::new(&bytes);
+/// assert!(properties.page());
+/// assert_eq!(properties.palette(), 5);
+/// assert_eq!(properties.priority(), 2);
+/// assert!(properties.flip_x());
+/// assert!(!properties.flip_y());
+///
+/// let mut properties = PropertiesMut::new(&mut bytes);
+/// properties.set_palette(2);
+/// properties.set_flip_y(true);
+/// assert_eq!(bytes, [0b1110_0101]);
+/// ```
+///
+/// # Conversions
+/// A field can be converted to any type implementing `From` for the value of the field, and
+/// whose value implements `From` for the type, by appending `as Type`. This works well with
+/// enums declared with [segment_enum](macro.segment_enum.html), which keep unknown values intact:
+/// ```rust
+/// use binseg::{segment_enum, segment_layout};
+///
+/// segment_enum! {
+///     pub enum Music: u8 {
+///         Overworld = 1,
+///         Athletic = 2,
+///     }
+/// }
+///
+/// segment_layout! {
+///     pub LevelHeader {
+///         music: u8 @ 0 bits 4..7 as Music
+///     }
+/// }
+///
+/// let mut bytes = [0x2f];
+///
+/// assert_eq!(LevelHeader::new(&bytes).music(), Music::Athletic);
+///
+/// let mut header = LevelHeaderMut::new(&mut bytes);
+/// header.set_music(Music::Unknown(5));
+/// assert_eq!(header.music(), Music::Unknown(5));
+/// assert_eq!(bytes, [0x5f]);
+/// ```
+#[macro_export]
+macro_rules! segment_layout {
+    (
+        $(#[$layout_attr:meta])*
+        pub $layout:ident {
+            $(
+                $(#[$meta_attr:meta])*
+                $field:ident : $ty:tt @ $offset:tt
+                $(bits $lo:tt .. $hi:tt)?
+                $(bit $bit:tt)?
+                $(as $conv:ty)?
+            ),* $(,)?
+        }
+    ) => (
+        $crate::paste::paste! {
+            $(#[$layout_attr])*
+            #[derive(Debug, Clone, Copy)]
+            pub struct $layout<'a> {
+                bytes: &'a [u8],
+            }
+
+            #[doc = "A writable view of a [`" $layout "`]."]
+            #[derive(Debug)]
+            pub struct [<$layout Mut>]<'a> {
+                bytes: &'a mut [u8],
+            }
+
+            $($crate::segment_layout!(@check $field $ty [$($lo $hi)?] [$($bit)?]);)*
+
+            impl<'a> $layout<'a> {
+                /// The size of a record in bytes.
+                pub const SIZE: usize = {
+                    let mut size = 0;
+                    $(
+                        let end = $offset + <$crate::segment_binary!(@codec $ty) as $crate::Codec>::SIZE;
+                        if end > size {
+                            size = end;
+                        }
+                    )*
+                    size
+                };
+
+                /// Creates a view of the record at the start of `bytes`.
+                ///
+                /// # Panics
+                /// Panics if `bytes` is shorter than a record.
+                pub fn new(bytes: &'a [u8]) -> $layout<'a> {
+                    assert!(bytes.len() >= $layout::SIZE, concat!("too few bytes for `", stringify!($layout), "`"));
+
+                    $layout { bytes }
+                }
+
+                /// Returns the bytes of the record.
+                pub fn as_bytes(&self) -> &'a [u8] {
+                    self.bytes
+                }
+
+                $(
+                    $(#[$meta_attr])*
+                    pub fn $field(&self) -> $crate::segment_layout!(@value $ty [$($lo $hi)?] [$($bit)?] [$($conv)?]) {
+                        let raw = <$crate::segment_binary!(@codec $ty) as $crate::Codec>::decode(
+                            &self.bytes[$offset..$offset + <$crate::segment_binary!(@codec $ty) as $crate::Codec>::SIZE],
+                        );
+
+                        $crate::segment_layout!(@from [$($conv)?] $crate::segment_layout!(@get raw [$($lo $hi)?] [$($bit)?]))
+                    }
+                )*
+            }
+
+            impl<'a> [<$layout Mut>]<'a> {
+                /// Creates a writable view of the record at the start of `bytes`.
+                ///
+                /// # Panics
+                /// Panics if `bytes` is shorter than a record.
+                pub fn new(bytes: &'a mut [u8]) -> [<$layout Mut>]<'a> {
+                    assert!(bytes.len() >= $layout::SIZE, concat!("too few bytes for `", stringify!($layout), "`"));
+
+                    [<$layout Mut>] { bytes }
+                }
+
+                /// Returns a read-only view of the record.
+                pub fn as_view(&self) -> $layout<'_> {
+                    $layout { bytes: self.bytes }
+                }
+
+                /// Returns the bytes of the record.
+                pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+                    self.bytes
+                }
+
+                $(
+                    $(#[$meta_attr])*
+                    pub fn $field(&self) -> $crate::segment_layout!(@value $ty [$($lo $hi)?] [$($bit)?] [$($conv)?]) {
+                        self.as_view().$field()
+                    }
+
+                    #[doc = "Sets the field `" $field "`."]
+                    pub fn [<set_ $field>](&mut self, value: $crate::segment_layout!(@value $ty [$($lo $hi)?] [$($bit)?] [$($conv)?])) {
+                        let value = $crate::segment_layout!(@into [$($conv)?] value);
+                        let bytes = &mut self.bytes[$offset..$offset + <$crate::segment_binary!(@codec $ty) as $crate::Codec>::SIZE];
+
+                        <$crate::segment_binary!(@codec $ty) as $crate::Codec>::encode(
+                            $crate::segment_layout!(@set $ty bytes value [$($lo $hi)?] [$($bit)?]),
+                            bytes,
+                        )
+                    }
+                )*
+            }
+        }
+    );
+
+    // The type of the value of a field.
+    (@value $ty:tt $bits:tt $bit:tt [$conv:ty]) => ($conv);
+    (@value $ty:tt [] [] []) => (<$crate::segment_binary!(@codec $ty) as $crate::Codec>::Value);
+    (@value $ty:tt [$lo:tt $hi:tt] [] []) => (<$crate::segment_binary!(@codec $ty) as $crate::Codec>::Value);
+    (@value $ty:tt [] [$bit:tt] []) => (bool);
+
+    // Converts between the value of a field and the type given with `as`.
+    (@from [] $value:expr) => ($value);
+    (@from [$conv:ty] $value:expr) => (<$conv>::from($value));
+    (@into [] $value:ident) => ($value);
+    (@into [$conv:ty] $value:ident) => ($value.into());
+
+    // Extracts the value of a field from the decoded value of its type.
+    (@get $raw:ident [] []) => ($raw);
+    (@get $raw:ident [$lo:tt $hi:tt] []) => ($crate::codec::Bits::get_bits($raw, $lo, $hi));
+    (@get $raw:ident [] [$bit:tt]) => ($crate::codec::Bits::get_bits($raw, $bit, $bit + 1) != 0);
+
+    // Merges the value of a field into the bytes of its type.
+    (@set $ty:tt $bytes:ident $value:ident [] []) => ($value);
+    (@set $ty:tt $bytes:ident $value:ident [$lo:tt $hi:tt] []) => (
+        $crate::codec::Bits::set_bits(
+            <$crate::segment_binary!(@codec $ty) as $crate::Codec>::decode($bytes),
+            $lo, $hi, $value,
+        )
+    );
+    (@set $ty:tt $bytes:ident $value:ident [] [$bit:tt]) => (
+        $crate::codec::Bits::set_bits(
+            <$crate::segment_binary!(@codec $ty) as $crate::Codec>::decode($bytes),
+            $bit, $bit + 1, $value.into(),
+        )
+    );
+
+    // Checks the bit range of a field at compile time.
+    (@check $field:ident $ty:tt [] []) => ();
+    (@check $field:ident $ty:tt [$lo:tt $hi:tt] []) => (
+        const _: () = assert!(
+            $lo < $hi && $hi <= <<$crate::segment_binary!(@codec $ty) as $crate::Codec>::Value as $crate::codec::Bits>::BITS,
+            concat!("the bits of `", stringify!($field), "` are out of range"),
+        );
+    );
+    (@check $field:ident $ty:tt [] [$bit:tt]) => (
+        $crate::segment_layout!(@check $field $ty [$bit ($bit + 1)] []);
+    );
+    (@check $field:ident $ty:tt [$lo:tt $hi:tt] [$bit:tt]) => (
+        compile_error!(concat!("`", stringify!($field), "` can not have both `bits` and `bit`"));
+    );
+}
+
+/// Declares an enum for the values of a field, keeping values without a variant intact.
+///
+/// Every variant is given the raw value it stands for. The macro adds an `Unknown(raw)` variant
+/// for all other values, and implements `From` in both directions, so the enum can be used with
+/// `as Enum` in a [segment_layout](macro.segment_layout.html). Converting an unknown value back
+/// results in the same raw value, so binaries using values the enum does not know about can be
+/// modified without losing them.
+///
+/// # Examples
+/// ```rust
+/// use binseg::segment_enum;
+///
+/// segment_enum! {
+///     /// The tileset of a level.
+///     pub enum Tileset: u8 {
+///         /// Normal 1
+///         Normal = 0x00,
+///         Castle = 0x01,
+///         Rope = 0x02,
+///     }
+/// }
+///
+/// assert_eq!(Tileset::from(0x01), Tileset::Castle);
+/// assert_eq!(Tileset::from(0x0e), Tileset::Unknown(0x0e));
+/// assert_eq!(u8::from(Tileset::Unknown(0x0e)), 0x0e);
+/// ```
+#[macro_export]
+macro_rules! segment_enum {
+    (
+        $(#[$enum_attr:meta])*
+        pub enum $enum_ident:ident : $repr:ty {
+            $(
+                $(#[$meta_attr:meta])*
+                $variant:ident = $value:literal
+            ),* $(,)?
+        }
+    ) => (
+        $(#[$enum_attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $enum_ident {
+            $(
+                $(#[$meta_attr])*
+                $variant,
+            )*
+            /// A value without a variant.
+            Unknown($repr),
+        }
+
+        impl From<$repr> for $enum_ident {
+            fn from(value: $repr) -> $enum_ident {
+                match value {
+                    $($value => $enum_ident::$variant,)*
+                    _ => $enum_ident::Unknown(value),
+                }
+            }
+        }
+
+        impl From<$enum_ident> for $repr {
+            fn from(value: $enum_ident) -> $repr {
+                match value {
+                    $($enum_ident::$variant => $value,)*
+                    $enum_ident::Unknown(value) => value,
+                }
+            }
         }
     );
 }
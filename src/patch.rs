@@ -0,0 +1,168 @@
+use crate::{bps, crc32, ips, snes, ups, Error};
+
+/// The size of the checksums at the end of BPS and UPS patches.
+const CHECKSUMS_LEN: usize = 12;
+
+/// Applies `patch` to `source` and returns the target. The format of the patch, IPS, BPS or UPS,
+/// is detected from its first bytes.
+///
+/// Returns an error if the patch is in none of these formats, is malformed or does not match the
+/// source.
+///
+/// # Examples
+/// ```rust
+/// use binseg::{apply_patch, bps};
+///
+/// let patch = bps::create(b"DEADBEEF", b"DEADC0DE");
+/// assert_eq!(apply_patch(&patch, b"DEADBEEF").unwrap(), b"DEADC0DE");
+///
+/// let patch = b"PATCH\x00\x00\x04\x00\x02C0EOF";
+/// assert_eq!(apply_patch(patch, b"DEADBEEF").unwrap(), b"DEADC0EF");
+/// ```
+pub fn apply_patch(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.starts_with(b"PATCH") {
+        ips::apply(patch, source)
+    } else if patch.starts_with(b"BPS1") {
+        bps::apply(patch, source)
+    } else if patch.starts_with(b"UPS1") {
+        ups::apply(patch, source)
+    } else {
+        Err(Error::InvalidPatch { offset: 0 })
+    }
+}
+
+/// Applies `patch` to `bin_data`, a binary whose copier header `copier_header` was stripped, and
+/// returns the copier header and the binary of the target. BPS and UPS patches made against the
+/// binary with its copier header are detected by their source size and applied to the headered
+/// binary. IPS patches can not be detected and have to be made against the unheadered binary.
+#[doc(hidden)]
+pub fn apply_patch_to_stripped(
+    patch: &[u8],
+    copier_header: Option<&[u8]>,
+    bin_data: &[u8],
+) -> Result<(Option<Vec<u8>>, Vec<u8>), Error> {
+    match copier_header {
+        Some(copier_header) if source_size(patch) == Some(copier_header.len() + bin_data.len()) => {
+            let target = apply_patch(patch, &[copier_header, bin_data].concat())?;
+            Ok(snes::strip_copier_header(target))
+        }
+        _ => Ok((
+            copier_header.map(<[u8]>::to_vec),
+            apply_patch(patch, bin_data)?,
+        )),
+    }
+}
+
+/// Returns the source size declared by a BPS or UPS patch.
+fn source_size(patch: &[u8]) -> Option<usize> {
+    if patch.starts_with(b"BPS1") || patch.starts_with(b"UPS1") {
+        Reader::new(patch, 4).number()
+    } else {
+        None
+    }
+}
+
+/// The checksums at the end of a BPS or UPS patch.
+pub(crate) struct Checksums {
+    pub source: u32,
+    pub target: u32,
+}
+
+/// Splits the checksums off a BPS or UPS patch starting with `header`, and verifies the checksum
+/// of the patch itself.
+pub(crate) fn split_checksums<'a>(
+    patch: &'a [u8],
+    header: &[u8],
+) -> Result<(&'a [u8], Checksums), Error> {
+    if patch.len() < header.len() + CHECKSUMS_LEN || !patch.starts_with(header) {
+        return Err(Error::InvalidPatch { offset: 0 });
+    }
+
+    let (body, footer) = patch.split_at(patch.len() - CHECKSUMS_LEN);
+    let checksum = |index: usize| {
+        let bytes = &footer[index * 4..index * 4 + 4];
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    };
+    verify_checksum("patch", checksum(2), crc32(&patch[..patch.len() - 4]))?;
+
+    Ok((
+        body,
+        Checksums {
+            source: checksum(0),
+            target: checksum(1),
+        },
+    ))
+}
+
+pub(crate) fn verify_checksum(file: &'static str, expected: u32, actual: u32) -> Result<(), Error> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(Error::PatchChecksumMismatch {
+            file,
+            expected,
+            actual,
+        })
+    }
+}
+
+/// Reads the numbers and bytes of a patch.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { data, pos }
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(count)?)?;
+        self.pos += count;
+
+        Some(bytes)
+    }
+
+    /// Reads a big endian number of `count` bytes.
+    pub fn be(&mut self, count: usize) -> Option<usize> {
+        let bytes = self.bytes(count)?;
+
+        Some(
+            bytes
+                .iter()
+                .fold(0, |number, &byte| (number << 8) | usize::from(byte)),
+        )
+    }
+
+    /// Reads a variable length number, seven bits per byte, ended by a byte with the highest bit
+    /// set.
+    pub fn number(&mut self) -> Option<usize> {
+        let mut number = 0usize;
+        let mut shift = 1usize;
+
+        loop {
+            let byte = self.bytes(1)?[0];
+            number = number.checked_add(usize::from(byte & 0x7f).checked_mul(shift)?)?;
+            if byte & 0x80 != 0 {
+                return Some(number);
+            }
+            shift = shift.checked_mul(0x80)?;
+            number = number.checked_add(shift)?;
+        }
+    }
+
+    /// Reads the distance of a copy from `offset`, moves `offset` past the copy of `len` bytes and
+    /// returns the start of the copy.
+    pub fn relative(&mut self, offset: &mut usize, len: usize) -> Option<usize> {
+        let number = self.number()?;
+        let start = if number & 1 != 0 {
+            offset.checked_sub(number >> 1)?
+        } else {
+            offset.checked_add(number >> 1)?
+        };
+        *offset = start.checked_add(len)?;
+
+        Some(start)
+    }
+}
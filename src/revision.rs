@@ -0,0 +1,125 @@
+use crate::{Error, HashAlgorithm};
+
+/// A check a binary has to pass to be identified as a revision.
+#[doc(hidden)]
+pub enum Check {
+    Digest(HashAlgorithm, &'static str),
+    Len(usize),
+    Magic(usize, &'static [u8]),
+    Validate(&'static str, fn(&[u8]) -> bool),
+}
+
+/// A revision of a binary, identified by a list of checks.
+#[doc(hidden)]
+pub struct Revision {
+    pub name: Option<&'static str>,
+    pub checks: &'static [Check],
+}
+
+/// Returns whether one of `revisions` is named `name`. Used in a const context to reject ranges
+/// of unknown revisions at compile time.
+#[doc(hidden)]
+pub const fn has_revision(revisions: &[Revision], name: &str) -> bool {
+    let mut i = 0;
+    while i < revisions.len() {
+        if let Some(other) = revisions[i].name {
+            if str_eq(other, name) {
+                return true;
+            }
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns the index of the first revision matching `bin_data`.
+#[doc(hidden)]
+pub fn identify(bin_data: &[u8], revisions: &[Revision]) -> Result<usize, Error> {
+    let mut digests: Vec<(HashAlgorithm, String)> = Vec::new();
+
+    let mut mismatches = Vec::new();
+    for (index, revision) in revisions.iter().enumerate() {
+        match check_revision(bin_data, revision, &mut digests) {
+            Ok(()) => return Ok(index),
+            Err(err) => mismatches.push(err),
+        }
+    }
+
+    if mismatches.len() == 1 {
+        Err(mismatches.remove(0))
+    } else {
+        Err(Error::NoMatchingRevision(mismatches))
+    }
+}
+
+fn check_revision(
+    bin_data: &[u8],
+    revision: &Revision,
+    digests: &mut Vec<(HashAlgorithm, String)>,
+) -> Result<(), Error> {
+    for check in revision.checks {
+        match *check {
+            Check::Digest(algorithm, expected) => {
+                let actual = match digests.iter().find(|(alg, _)| *alg == algorithm) {
+                    Some((_, digest)) => digest.clone(),
+                    None => {
+                        let digest = algorithm.hex_digest(bin_data);
+                        digests.push((algorithm, digest.clone()));
+                        digest
+                    }
+                };
+
+                if !expected.eq_ignore_ascii_case(&actual) {
+                    return Err(Error::HashMismatch {
+                        algorithm,
+                        expected: String::from(expected),
+                        actual,
+                    });
+                }
+            }
+            Check::Len(expected) => {
+                if bin_data.len() != expected {
+                    return Err(Error::LengthMismatch {
+                        expected,
+                        actual: bin_data.len(),
+                    });
+                }
+            }
+            Check::Magic(offset, expected) => {
+                let actual = bin_data.get(offset..).unwrap_or(&[]);
+                let actual = &actual[..expected.len().min(actual.len())];
+
+                if actual != expected {
+                    return Err(Error::MagicMismatch {
+                        offset,
+                        expected,
+                        actual: actual.to_vec(),
+                    });
+                }
+            }
+            Check::Validate(validator, validate) => {
+                if !validate(bin_data) {
+                    return Err(Error::ValidationFailed { validator });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
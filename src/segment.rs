@@ -0,0 +1,217 @@
+use crate::{
+    codec::u24le,
+    snes::{self, Mapping},
+    Codec, Error,
+};
+use std::ops::{Bound, Range, RangeBounds};
+
+/// Resolves the range of the segment `segment` against a binary of length `len`. If a mapping is
+/// given, the range is made of SNES addresses and has to be bounded.
+///
+/// Returns an error if the range is reversed, can not be mapped or reaches past the end of the
+/// binary.
+#[doc(hidden)]
+pub fn resolve_range<R: RangeBounds<usize>>(
+    segment: &'static str,
+    range: R,
+    mapping: Option<Mapping>,
+    len: usize,
+) -> Result<Range<usize>, Error> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => Some(start),
+        Bound::Excluded(&start) => Some(start.saturating_add(1)),
+        Bound::Unbounded => None,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => Some(end.saturating_add(1)),
+        Bound::Excluded(&end) => Some(end),
+        Bound::Unbounded => None,
+    };
+
+    let (start, end) = match (mapping, start, end) {
+        (Some(mapping), Some(start), Some(end)) => {
+            let range = snes::map_range(segment, start..end, mapping)?;
+            (range.start, range.end)
+        }
+        (Some(_), start, end) => {
+            return Err(Error::UnmappedSegment {
+                segment,
+                range: start.unwrap_or(0)..end.unwrap_or(usize::MAX),
+            })
+        }
+        (None, start, end) => (start.unwrap_or(0), end.unwrap_or(len)),
+    };
+
+    if start > end || end > len {
+        return Err(Error::SegmentOutOfBounds {
+            segment,
+            range: start..end,
+            len,
+        });
+    }
+
+    Ok(start..end)
+}
+
+/// Resolves the range of the table `segment` against a binary of length `len`. The stride is
+/// checked to be non-zero when the segmenter is compiled.
+#[doc(hidden)]
+pub fn resolve_table(
+    segment: &'static str,
+    base: usize,
+    stride: usize,
+    count: usize,
+    mapping: Option<Mapping>,
+    len: usize,
+) -> Result<Range<usize>, Error> {
+    let end = stride
+        .checked_mul(count)
+        .and_then(|size| base.checked_add(size))
+        .unwrap_or(usize::MAX);
+
+    resolve_range(segment, base..end, mapping, len)
+}
+
+/// Resolves the range of the segment `segment`, which starts at the 24 bit pointer number `index`
+/// of the segment `pointers` and is `size` bytes long. If a mapping is given, the pointer is a SNES
+/// address.
+///
+/// Returns an error if the pointer lies outside of `pointers` or its target can not be resolved.
+#[doc(hidden)]
+pub fn resolve_pointer(
+    segment: &'static str,
+    pointers: (&'static str, &Range<usize>),
+    index: usize,
+    size: usize,
+    mapping: Option<Mapping>,
+    bin_data: &[u8],
+) -> Result<Range<usize>, Error> {
+    let (source, range) = pointers;
+    let offset = index
+        .checked_mul(3)
+        .and_then(|offset| range.start.checked_add(offset))
+        .filter(|&offset| offset.saturating_add(3) <= range.end)
+        .ok_or(Error::PointerOutOfBounds {
+            segment,
+            source,
+            index,
+        })?;
+
+    let target = <u24le as Codec>::decode(&bin_data[offset..offset + 3]) as usize;
+
+    resolve_range(
+        segment,
+        target..target.saturating_add(size),
+        mapping,
+        bin_data.len(),
+    )
+}
+
+/// Resolves the ranges of the segment `segment`, one for every 24 bit pointer in `pointers`, each
+/// `size` bytes long. If a mapping is given, the pointers are SNES addresses.
+///
+/// Returns an error naming the index of the first pointer whose target can not be resolved.
+#[doc(hidden)]
+pub fn resolve_pointer_table(
+    segment: &'static str,
+    pointers: &Range<usize>,
+    size: usize,
+    mapping: Option<Mapping>,
+    bin_data: &[u8],
+) -> Result<Vec<Range<usize>>, Error> {
+    bin_data[pointers.clone()]
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(index, pointer)| {
+            let target = <u24le as Codec>::decode(pointer) as usize;
+            let range = target..target.saturating_add(size);
+
+            resolve_range(segment, range.clone(), mapping, bin_data.len()).map_err(|_| {
+                Error::InvalidPointer {
+                    segment,
+                    index,
+                    range,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Resolves the range of the segment `segment`, which starts at `start` and ends after the first
+/// occurrence of `terminator`, at most `max` bytes after the start.
+///
+/// Returns an error if the start can not be resolved or there is no terminator.
+#[doc(hidden)]
+pub fn resolve_terminated(
+    segment: &'static str,
+    start: usize,
+    terminator: &[u8],
+    max: Option<usize>,
+    mapping: Option<Mapping>,
+    bin_data: &[u8],
+) -> Result<Range<usize>, Error> {
+    let offset = resolve_range(segment, start..start, mapping, bin_data.len())?.start;
+    let end = max
+        .and_then(|max| offset.checked_add(max))
+        .map_or(bin_data.len(), |end| end.min(bin_data.len()));
+
+    let size = bin_data[offset..end]
+        .windows(terminator.len())
+        .position(|window| window == terminator)
+        .ok_or(Error::MissingTerminator {
+            segment,
+            range: offset..end,
+        })?
+        + terminator.len();
+
+    resolve_range(segment, start..start + size, mapping, bin_data.len())
+}
+
+/// A segment whose range was given as literals, checked at compile time.
+#[doc(hidden)]
+pub struct LiteralSegment {
+    pub start: usize,
+    pub end: usize,
+    pub alias: bool,
+    pub reversed_msg: &'static str,
+    pub overlap_msg: &'static str,
+}
+
+/// Panics if a literal segment range is reversed or overlaps another segment that is not marked
+/// as an alias. With a mapping, the ranges are compared by the rom bytes they map to, so mirrored
+/// addresses of the same bytes overlap. Ranges that do not map are left to be reported at load
+/// time. Called in a const context, so the panic becomes a compile error.
+#[doc(hidden)]
+pub const fn check_literal_segments(segments: &[Option<LiteralSegment>], mapping: Option<Mapping>) {
+    let mut i = 0;
+    while i < segments.len() {
+        if let Some(seg) = &segments[i] {
+            if seg.start > seg.end {
+                panic!("{}", seg.reversed_msg);
+            }
+
+            if let Some((start, end)) = map_literal(seg, mapping) {
+                let mut j = 0;
+                while j < i {
+                    if let Some(other) = &segments[j] {
+                        if let Some((other_start, other_end)) = map_literal(other, mapping) {
+                            let overlaps = start < other_end && other_start < end;
+                            if overlaps && !seg.alias && !other.alias {
+                                panic!("{}", seg.overlap_msg);
+                            }
+                        }
+                    }
+                    j += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+}
+
+const fn map_literal(seg: &LiteralSegment, mapping: Option<Mapping>) -> Option<(usize, usize)> {
+    match mapping {
+        Some(mapping) => snes::map_offsets(seg.start, seg.end, mapping),
+        None => Some((seg.start, seg.end)),
+    }
+}
@@ -0,0 +1,327 @@
+//! Helpers for Super Nintendo roms.
+//!
+//! Documentation and disassemblies of SNES games refer to rom locations by their address on the
+//! SNES bus, like `$0DA415`. How these addresses map to offsets into the rom file depends on the
+//! memory map of the cartridge, described by a [`Mapping`]. All offsets are offsets into a rom
+//! without a copier header, see [`strip_copier_header`].
+
+use crate::{segment_enum, segment_layout, Error};
+use std::ops::Range;
+
+/// The memory map of a SNES cartridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mapping {
+    /// Mode 20, rom in 32 KiB banks in the upper half of banks `$00-$7D` and `$80-$FF`.
+    LoRom,
+    /// Mode 21, rom in 64 KiB banks `$C0-$FF`, mirrored in the upper half of banks `$00-$3F`
+    /// and `$80-$BF`.
+    HiRom,
+    /// Mode 25, a LoRom of up to 8 MiB. The first 4 MiB are mapped to banks `$80-$FF`.
+    ExLoRom,
+    /// Mode 25, a HiRom of up to 8 MiB. The first 4 MiB are mapped to banks `$C0-$FF`.
+    ExHiRom,
+    /// A cartridge with an SA-1 chip and the default Super MMC banks, mapping 4 MiB like a LoRom
+    /// to banks `$00-$3F` and `$80-$BF`, and like a HiRom to banks `$C0-$FF`.
+    Sa1,
+}
+
+/// The address of the internal header, which is the same for every mapping.
+pub const HEADER_ADDRESS: u32 = 0x00FFC0;
+
+/// The length of a copier header.
+pub const COPIER_HEADER_LEN: usize = 0x200;
+
+/// The Super MMC banks of an SA-1 cartridge at power on, indexed by the bank of the address.
+const SA1_BANKS: [Option<usize>; 8] = [
+    Some(0 << 20),
+    Some(1 << 20),
+    None,
+    None,
+    Some(2 << 20),
+    Some(3 << 20),
+    None,
+    None,
+];
+
+/// Converts an address on the SNES bus to an offset into the rom.
+///
+/// Returns `None` if the address does not map to the rom, like addresses of RAM or of hardware
+/// registers.
+///
+/// # Examples
+/// ```rust
+/// use binseg::snes::{snes_to_pc, Mapping};
+///
+/// assert_eq!(snes_to_pc(0x0DA415, Mapping::LoRom), Some(0x06A415));
+/// assert_eq!(snes_to_pc(0xC0FFC0, Mapping::HiRom), Some(0x00FFC0));
+/// assert_eq!(snes_to_pc(0x7E0000, Mapping::LoRom), None);
+/// ```
+pub const fn snes_to_pc(address: u32, mapping: Mapping) -> Option<usize> {
+    let address = address as usize;
+    if address > 0xFF_FFFF || address & 0xFE_0000 == 0x7E_0000 || address & 0x40_8000 == 0 {
+        return None;
+    }
+
+    let lorom = ((address & 0x7F_0000) >> 1) | (address & 0x7FFF);
+    match mapping {
+        Mapping::LoRom if address & 0x70_8000 == 0x70_0000 => None,
+        Mapping::LoRom => Some(lorom),
+        Mapping::HiRom => Some(address & 0x3F_FFFF),
+        Mapping::ExLoRom if address & 0xF0_0000 == 0x70_0000 => None,
+        Mapping::ExLoRom if address & 0x80_0000 == 0 => Some(lorom + 0x40_0000),
+        Mapping::ExLoRom => Some(lorom),
+        Mapping::ExHiRom if address & 0x80_0000 == 0 => Some((address & 0x3F_FFFF) | 0x40_0000),
+        Mapping::ExHiRom => Some(address & 0x3F_FFFF),
+        Mapping::Sa1 if address & 0x40_8000 == 0x00_8000 => match SA1_BANKS[address >> 21] {
+            Some(bank) => Some(bank | ((address & 0x1F_0000) >> 1) | (address & 0x7FFF)),
+            None => None,
+        },
+        Mapping::Sa1 if address & 0xC0_0000 == 0xC0_0000 => {
+            match SA1_BANKS[((address & 0x10_0000) >> 20) | ((address & 0x20_0000) >> 19)] {
+                Some(bank) => Some(bank | (address & 0x0F_FFFF)),
+                None => None,
+            }
+        }
+        Mapping::Sa1 => None,
+    }
+}
+
+/// Converts an offset into the rom to an address on the SNES bus.
+///
+/// Returns `None` if the offset is too large for the mapping.
+///
+/// # Examples
+/// ```rust
+/// use binseg::snes::{pc_to_snes, Mapping};
+///
+/// assert_eq!(pc_to_snes(0x06A415, Mapping::LoRom), Some(0x0DA415));
+/// assert_eq!(pc_to_snes(0x00FFC0, Mapping::HiRom), Some(0xC0FFC0));
+/// assert_eq!(pc_to_snes(0x400000, Mapping::LoRom), None);
+/// ```
+pub fn pc_to_snes(offset: usize, mapping: Mapping) -> Option<u32> {
+    let lorom = ((offset << 1) & 0x7F_0000) | (offset & 0x7FFF) | 0x8000;
+
+    let address = match mapping {
+        Mapping::LoRom if lorom & 0xF0_0000 == 0x70_0000 => lorom | 0x80_0000,
+        Mapping::LoRom => lorom,
+        Mapping::HiRom => offset | 0xC0_0000,
+        Mapping::ExLoRom if offset & 0x40_0000 != 0 => lorom & 0x7F_FFFF,
+        Mapping::ExLoRom => lorom | 0x80_0000,
+        // Banks $7E and $7F are RAM, so the end of the rom is only mirrored in banks $3E and $3F.
+        Mapping::ExHiRom if offset >= 0x7E_0000 => offset & 0x3F_FFFF,
+        Mapping::ExHiRom if offset & 0x40_0000 != 0 => offset,
+        Mapping::ExHiRom => offset | 0xC0_0000,
+        Mapping::Sa1 => {
+            let bank = SA1_BANKS
+                .iter()
+                .position(|&bank| bank == Some(offset & 0x70_0000))?;
+
+            0x8000 | (bank << 21) | ((offset & 0x0F_8000) << 1) | (offset & 0x7FFF)
+        }
+    };
+
+    // Offsets past the end of the mapping wrap around, and some of them end up in RAM.
+    if snes_to_pc(address as u32, mapping) == Some(offset) {
+        Some(address as u32)
+    } else {
+        None
+    }
+}
+
+segment_layout! {
+    /// The internal header of a SNES rom, found at [`HEADER_ADDRESS`].
+    pub InternalHeader {
+        /// The title of the game, padded with spaces.
+        title: [u8; 21] @ 0x00,
+        /// The map mode, like `0x20` for a LoRom or `0x21` for a HiRom.
+        map_mode: u8 @ 0x15,
+        /// Whether the rom can be accessed at the faster speed.
+        fast_rom: u8 @ 0x15 bit 4,
+        /// The chips on the cartridge.
+        cartridge_type: u8 @ 0x16,
+        /// The size of the rom, as a power of two of kilobytes.
+        rom_size: u8 @ 0x17,
+        /// The size of the SRAM, as a power of two of kilobytes.
+        sram_size: u8 @ 0x18,
+        /// The region the game was released in.
+        region: u8 @ 0x19 as Region,
+        /// The developer of the game.
+        developer: u8 @ 0x1a,
+        /// The version of the game.
+        version: u8 @ 0x1b,
+        /// The bitwise complement of the checksum.
+        checksum_complement: u16le @ 0x1c,
+        /// The checksum of the rom, see [`checksum`].
+        checksum: u16le @ 0x1e
+    }
+}
+
+impl InternalHeader<'_> {
+    /// Returns the size of the rom in bytes, as declared by the header.
+    pub fn rom_len(&self) -> usize {
+        0x400usize.checked_shl(self.rom_size().into()).unwrap_or(0)
+    }
+
+    /// Returns the size of the SRAM in bytes, as declared by the header.
+    pub fn sram_len(&self) -> usize {
+        match self.sram_size() {
+            0 => 0,
+            sram_size => 0x400usize.checked_shl(sram_size.into()).unwrap_or(0),
+        }
+    }
+}
+
+segment_enum! {
+    /// The region of a SNES game.
+    pub enum Region: u8 {
+        /// Japan
+        Japan = 0x00,
+        /// North America
+        NorthAmerica = 0x01,
+        /// Europe
+        Europe = 0x02,
+        /// Sweden and Scandinavia
+        Sweden = 0x03,
+        /// Finland
+        Finland = 0x04,
+        /// Denmark
+        Denmark = 0x05,
+        /// France
+        France = 0x06,
+        /// The Netherlands
+        Netherlands = 0x07,
+        /// Spain
+        Spain = 0x08,
+        /// Germany, Austria and Switzerland
+        Germany = 0x09,
+        /// Italy
+        Italy = 0x0a,
+        /// China and Hong Kong
+        China = 0x0b,
+        /// Indonesia
+        Indonesia = 0x0c,
+        /// South Korea
+        Korea = 0x0d,
+        /// All regions
+        International = 0x0e,
+        /// Canada
+        Canada = 0x0f,
+        /// Brazil
+        Brazil = 0x10,
+        /// Australia
+        Australia = 0x11
+    }
+}
+
+/// Computes the checksum of a rom, the sum of all its bytes.
+///
+/// A rom whose size is not a power of two is mirrored up to the next power of two, like the
+/// cartridge does. The checksum and its complement in the internal header add the same amount to
+/// the sum for any checksum, so the checksum of a rom with a correct internal header is the checksum
+/// stored in it.
+///
+/// # Examples
+/// ```rust
+/// use binseg::snes::checksum;
+///
+/// assert_eq!(checksum(&[0x01; 0x400]), 0x400);
+/// // The last 0x400 bytes are mirrored once.
+/// assert_eq!(checksum(&[0x01; 0xc00]), 0x1000);
+/// ```
+pub fn checksum(rom: &[u8]) -> u16 {
+    mirrored_sum(rom) as u16
+}
+
+/// Sums up the bytes of `bytes`, mirrored up to the next power of two.
+fn mirrored_sum(bytes: &[u8]) -> u32 {
+    if bytes.is_empty() {
+        return 0;
+    }
+
+    let base = 1 << (usize::BITS - 1 - bytes.len().leading_zeros());
+    let sum = bytes[..base]
+        .iter()
+        .fold(0u32, |sum, &byte| sum.wrapping_add(byte.into()));
+    let rest = &bytes[base..];
+    if rest.is_empty() {
+        return sum;
+    }
+
+    let mirrors = (base / rest.len().next_power_of_two()) as u32;
+    sum.wrapping_add(mirrored_sum(rest).wrapping_mul(mirrors))
+}
+
+/// Splits the copier header off a rom dump.
+///
+/// Roms are always a multiple of 1 KiB long, so a dump whose length is 512 bytes past a multiple of
+/// 1 KiB starts with a copier header. Returns the header, if there is one, and the rom without it.
+///
+/// # Examples
+/// ```rust
+/// use binseg::snes::strip_copier_header;
+///
+/// let mut dump = vec![0x00; 0x200];
+/// dump.extend_from_slice(&[0xff; 0x400]);
+///
+/// let (copier_header, rom) = strip_copier_header(dump);
+/// assert_eq!(copier_header, Some(vec![0x00; 0x200]));
+/// assert_eq!(rom, [0xff; 0x400]);
+///
+/// let (copier_header, rom) = strip_copier_header(rom);
+/// assert_eq!(copier_header, None);
+/// assert_eq!(rom, [0xff; 0x400]);
+/// ```
+pub fn strip_copier_header(mut dump: Vec<u8>) -> (Option<Vec<u8>>, Vec<u8>) {
+    if dump.len() % 0x400 != COPIER_HEADER_LEN {
+        return (None, dump);
+    }
+
+    let copier_header = dump.drain(..COPIER_HEADER_LEN).collect();
+
+    (Some(copier_header), dump)
+}
+
+/// Converts a range of addresses on the SNES bus to a range of offsets into the rom.
+///
+/// Returns an error if the range does not map to a contiguous range of the rom.
+#[doc(hidden)]
+pub fn map_range(
+    segment: &'static str,
+    range: Range<usize>,
+    mapping: Mapping,
+) -> Result<Range<usize>, Error> {
+    match map_offsets(range.start, range.end, mapping) {
+        Some((start, end)) => Ok(start..end),
+        None => Err(Error::UnmappedSegment { segment, range }),
+    }
+}
+
+/// Converts the addresses `start..end` to the offsets of a contiguous range of the rom, usable in
+/// const contexts.
+pub(crate) const fn map_offsets(
+    start: usize,
+    end: usize,
+    mapping: Mapping,
+) -> Option<(usize, usize)> {
+    if start > end {
+        return None;
+    }
+
+    let first = match snes_to_pc(start as u32, mapping) {
+        Some(first) => first,
+        None => return None,
+    };
+    if start == end {
+        return Some((first, first));
+    }
+
+    let last = match snes_to_pc((end - 1) as u32, mapping) {
+        Some(last) => last,
+        None => return None,
+    };
+    if last < first || last - first != end - 1 - start {
+        return None;
+    }
+
+    Some((first, last + 1))
+}
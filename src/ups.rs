@@ -0,0 +1,114 @@
+//! Patches in the UPS format.
+//!
+//! A UPS patch lists the changed bytes of the target, XORed with the bytes of the source, and ends
+//! with the CRC32 checksums of the source, the target and the patch itself.
+use crate::{
+    crc32,
+    patch::{split_checksums, verify_checksum, Reader},
+    Error,
+};
+
+const HEADER: &[u8] = b"UPS1";
+
+/// Applies `patch` to `source` and returns the target.
+///
+/// Returns an error if the patch is malformed, or if the checksum of the patch, the source or the
+/// target does not match.
+///
+/// # Examples
+/// ```rust
+/// use binseg::{crc32, ups};
+///
+/// let source = [0x00; 4];
+/// let target = [0x00, 0x12, 0x00, 0x00, 0x34];
+///
+/// // Sizes 4 and 5, then twice skips a byte and XORs the next one. The zero ending a run of XORed
+/// // bytes leaves its byte unchanged.
+/// let mut patch = b"UPS1\x84\x85\x81\x12\x00\x81\x34\x00".to_vec();
+/// patch.extend_from_slice(&crc32(&source).to_le_bytes());
+/// patch.extend_from_slice(&crc32(&target).to_le_bytes());
+/// patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+///
+/// assert_eq!(ups::apply(&patch, &source).unwrap(), target);
+/// ```
+///
+/// A patch declaring a target too large to allocate is rejected:
+/// ```rust
+/// use binseg::{crc32, ups, Error};
+///
+/// let source = [0x00; 4];
+/// let mut patch = b"UPS1\x84\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x7f\x80".to_vec();
+/// patch.extend_from_slice(&crc32(&source).to_le_bytes());
+/// patch.extend_from_slice(&[0x00; 4]);
+/// patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+///
+/// assert!(matches!(ups::apply(&patch, &source), Err(Error::InvalidPatch { offset: 5 })));
+/// ```
+///
+/// A patch shrinking the file ignores the XORed bytes past the end of the target:
+/// ```rust
+/// use binseg::{crc32, ups};
+///
+/// let source = [0x01, 0x02, 0x03, 0x04];
+/// let target = [0x01, 0x02];
+///
+/// // Sizes 4 and 2, then skips two bytes and XORs the removed bytes with zero.
+/// let mut patch = b"UPS1\x84\x82\x82\x03\x04\x00".to_vec();
+/// patch.extend_from_slice(&crc32(&source).to_le_bytes());
+/// patch.extend_from_slice(&crc32(&target).to_le_bytes());
+/// patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+///
+/// assert_eq!(ups::apply(&patch, &source).unwrap(), target);
+/// ```
+pub fn apply(patch: &[u8], source: &[u8]) -> Result<Vec<u8>, Error> {
+    let (body, checksums) = split_checksums(patch, HEADER)?;
+    verify_checksum("source", checksums.source, crc32(source))?;
+
+    let mut reader = Reader::new(body, HEADER.len());
+    let invalid = |offset| Error::InvalidPatch { offset };
+
+    let source_size = reader.number().ok_or_else(|| invalid(reader.pos))?;
+    let target_size_offset = reader.pos;
+    let target_size = reader.number().ok_or_else(|| invalid(target_size_offset))?;
+    if source_size != source.len() {
+        return Err(Error::LengthMismatch {
+            expected: source_size,
+            actual: source.len(),
+        });
+    }
+
+    // The target size comes from the patch, so a size that can not be allocated is an error.
+    let mut target = source.to_vec();
+    target
+        .try_reserve_exact(target_size.saturating_sub(source.len()))
+        .map_err(|_| invalid(target_size_offset))?;
+    target.resize(target_size, 0x00);
+
+    // Every run of XORed bytes is ended by a zero, which leaves its byte unchanged. Patches that
+    // shrink the file also XOR the bytes past the end of the target, so they can be reversed.
+    let mut pos: usize = 0;
+    while reader.pos < body.len() {
+        let offset = reader.pos;
+        pos = reader
+            .number()
+            .and_then(|skip| pos.checked_add(skip))
+            .ok_or_else(|| invalid(offset))?;
+
+        loop {
+            let offset = reader.pos;
+            let byte = reader.bytes(1).ok_or_else(|| invalid(offset))?[0];
+            pos = pos.saturating_add(1);
+            if byte == 0x00 {
+                break;
+            }
+
+            if let Some(target_byte) = target.get_mut(pos - 1) {
+                *target_byte ^= byte;
+            }
+        }
+    }
+
+    verify_checksum("target", checksums.target, crc32(&target))?;
+
+    Ok(target)
+}